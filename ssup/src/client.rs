@@ -1,29 +1,143 @@
-use crate::constants::USER_AGENT;
-use crate::credential::{Credential, ResponseData, ResponseValue};
+use crate::config::ClientConfig;
+use crate::credential::Credential;
+use crate::error::{Error, Result};
+use crate::limit::{ChunkBudget, RateLimiter};
 use crate::line::UploadLine;
-use crate::video::{EditVideo, EditVideoPart, Video, VideoCardItem, VideoId, VideoPart};
-use anyhow::bail;
+use crate::progress::UploadEvent;
+use crate::uploader::chunk;
+use crate::uploader::upos::{self, UposSession};
+use crate::video::{
+    ArchiveItem, ArchivePage, ArchiveStatus, EditVideo, EditVideoPart, SubmitResult, Video,
+    VideoCardItem, VideoId, VideoPart,
+};
+use md5::{Digest, Md5};
 use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::Url;
 use reqwest_cookie_store::CookieStoreMutex;
+use serde::Deserialize;
 use serde_json::{json, Value};
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::fs;
+use tokio::io::AsyncRead;
 use tokio::sync::mpsc::Sender;
 
-/// 上传使用的客户端
-pub struct Client {
-    pub(crate) client: reqwest::Client,
-    cookie_store: Arc<CookieStoreMutex>,
-
-    line: UploadLine,
-    credential: Credential,
+/// [`Client`] 的构造器
+#[derive(Default)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    lines: Vec<UploadLine>,
+    session_dir: Option<PathBuf>,
 }
 
-impl Client {
-    pub fn new(upload_line: UploadLine, credential: Credential) -> Self {
+impl ClientBuilder {
+    /// 替换全部配置
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.config.user_agent = user_agent.into();
+        self
+    }
+
+    /// 设置单个分P同时上传的分块数
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        if concurrency > 0 {
+            self.config.concurrency = concurrency;
+        }
+        self
+    }
+
+    /// 设置单个分P同时在内存中的分块数据上限，单位为字节
+    pub fn max_in_flight(mut self, bytes: usize) -> Self {
+        self.config.max_in_flight = bytes;
+        self
+    }
+
+    /// 设置接口请求的超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// 设置上传分块的超时时间
+    pub fn upload_timeout(mut self, timeout: Duration) -> Self {
+        self.config.upload_timeout = timeout;
+        self
+    }
+
+    /// 设置上传分块的重试次数与重试间隔
+    pub fn retry(
+        mut self,
+        max_retries: u32,
+        min_interval: Duration,
+        max_interval: Duration,
+    ) -> Self {
+        self.config.max_retries = max_retries;
+        self.config.min_retry_interval = min_interval;
+        self.config.max_retry_interval = max_interval;
+        self
+    }
+
+    /// 设置所有分P共享的限速器，可与其他客户端共用
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.config.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// 设置所有分P共享的同时上传分块数上限，可与其他客户端共用
+    pub fn chunk_budget(mut self, chunk_budget: ChunkBudget) -> Self {
+        self.config.chunk_budget = chunk_budget;
+        self
+    }
+
+    /// 设置单个分P的上传速率上限，单位为字节每秒
+    pub fn part_rate_limit(mut self, rate: u64) -> Self {
+        self.config.part_rate_limit = Some(rate);
+        self
+    }
+
+    pub fn proxy<S>(mut self, proxy: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.config.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 添加上传线路，线路上传失败时按添加顺序切换
+    pub fn line(mut self, line: UploadLine) -> Self {
+        self.lines.push(line);
+        self
+    }
+
+    /// 添加多条上传线路
+    pub fn lines<I>(mut self, lines: I) -> Self
+    where
+        I: IntoIterator<Item = UploadLine>,
+    {
+        self.lines.extend(lines);
+        self
+    }
+
+    /// 设置断点续传会话文件所在的目录
+    pub fn session_dir<P>(mut self, session_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.session_dir = Some(session_dir.into());
+        self
+    }
+
+    pub fn build(self, credential: Credential) -> Result<Client> {
         let mut headers = HeaderMap::new();
         headers.insert(
             "Referer",
@@ -35,25 +149,80 @@ impl Client {
         let cookie_store = CookieStoreMutex::new(cookie_store);
         let cookie_store = Arc::new(cookie_store);
 
-        let mut me = Self {
-            client: reqwest::Client::builder()
+        let mut lines = self.lines;
+        if lines.is_empty() {
+            lines.push(UploadLine::default());
+        }
+
+        let mut client = Client {
+            client: self
+                .config
+                .http_builder()?
                 .cookie_provider(cookie_store.clone())
-                .user_agent(USER_AGENT.read().as_str())
                 .default_headers(headers)
-                .timeout(Duration::new(60, 0))
-                .build()
-                .unwrap(),
+                .build()?,
             cookie_store,
-            line: upload_line,
+            config: self.config,
+            lines,
             credential,
+            session_dir: self.session_dir,
+            md5_cache: Mutex::default(),
         };
 
-        me.load_credential();
-        me
+        client.load_credential();
+        Ok(client)
     }
+}
 
-    pub async fn auto(credential: Credential) -> anyhow::Result<Self> {
-        Ok(Self::new(UploadLine::auto().await?, credential))
+/// 上传使用的客户端
+pub struct Client {
+    pub(crate) client: reqwest::Client,
+    cookie_store: Arc<CookieStoreMutex>,
+    pub(crate) config: ClientConfig,
+
+    /// 按优先级排列的候选线路
+    lines: Vec<UploadLine>,
+    credential: Credential,
+
+    /// 断点续传会话文件所在目录，为空时保存在视频文件旁
+    session_dir: Option<PathBuf>,
+    /// 已计算的文件 MD5，以文件的修改时间与大小判断是否仍然有效
+    md5_cache: Mutex<HashMap<PathBuf, (u64, usize, String)>>,
+}
+
+impl Client {
+    pub fn new(upload_line: UploadLine, credential: Credential) -> Self {
+        Self::builder()
+            .line(upload_line)
+            .build(credential)
+            .expect("failed to build client with default config")
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    pub async fn auto(credential: Credential) -> Result<Self> {
+        let config = ClientConfig::default();
+        let line = UploadLine::auto(&config).await?;
+        Self::builder().config(config).line(line).build(credential)
+    }
+
+    /// 获取视频文件对应的会话文件路径
+    pub(crate) fn session_path(&self, video: &Path) -> PathBuf {
+        match &self.session_dir {
+            Some(dir) => {
+                // 以文件的绝对路径区分同名文件
+                let path = video.canonicalize().unwrap_or_else(|_| video.to_path_buf());
+                let hash = Md5::digest(path.to_string_lossy().as_bytes());
+                dir.join(format!("{hash:x}.upos.json"))
+            }
+            None => {
+                let mut name = video.file_name().unwrap_or_default().to_os_string();
+                name.push(".upos.json");
+                video.with_file_name(name)
+            }
+        }
     }
 
     /// 加载 LoginInfo 进入 Client
@@ -66,16 +235,16 @@ impl Client {
     }
 
     /// 上传封面
-    pub async fn upload_cover<P>(&self, cover: P) -> anyhow::Result<String>
+    pub async fn upload_cover<P>(&self, cover: P) -> Result<String>
     where
         P: AsRef<Path>,
     {
         let cover = fs::read(cover).await?;
 
-        let csrf = self.credential.cookie_info.get("bili_jct").unwrap();
-        let response: ResponseData = self
+        let csrf = self.credential.csrf()?;
+        let response: Value = self
             .client
-            .post("https://member.bilibili.com/x/vu/web/cover/up")
+            .post(format!("{}/x/vu/web/cover/up", self.config.member_url))
             .form(&json!({
                 "cover": format!("data:image/jpeg;base64,{}", base64::encode(cover)),
                 "csrf": csrf,
@@ -84,124 +253,302 @@ impl Client {
             .await?
             .json()
             .await?;
-        match &response {
-            ResponseData {
-                data: ResponseValue::Value(value),
-                ..
-            } if value.is_null() => bail!("{response:?}"),
-            ResponseData {
-                data: ResponseValue::Value(value),
-                ..
-            } => {
-                return Ok(value["url"]
-                    .as_str()
-                    .ok_or(anyhow::anyhow!("cover_up error"))?
-                    .into())
-            }
-            _ => {
-                unreachable!()
-            }
-        };
+        Error::check(&response)?;
+        response["data"]["url"]
+            .as_str()
+            .map(|url| url.to_string())
+            .ok_or_else(|| Error::UnexpectedResponse(response.to_string()))
     }
 
     /// 上传单个分P
+    ///
+    /// 依次尝试候选线路，线路因网络或服务端错误上传失败时自动切换到下一条线路重新上传
     pub async fn upload_video_part<P>(
         &self,
         video: P,
         total_size: usize,
-        sx: Sender<usize>,
+        sx: Sender<UploadEvent>,
         part_name: Option<String>,
-    ) -> anyhow::Result<VideoPart>
+    ) -> Result<VideoPart>
     where
         P: AsRef<Path>,
     {
-        let mut part = self.line.upload(self, video, total_size, sx).await?;
+        let video = video.as_ref();
+        let upload = self.upload_on_lines(video, total_size, sx, part_name);
+        self.with_md5(video, total_size, upload).await
+    }
+
+    async fn upload_on_lines(
+        &self,
+        video: &Path,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+        part_name: Option<String>,
+    ) -> Result<VideoPart> {
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        // 存在未完成的会话时优先在原线路上续传
+        if let Some(session) = UposSession::load(self.session_path(video)).await {
+            if let Some(i) = lines.iter().position(|line| line.name() == session.line()) {
+                let line = lines.remove(i);
+                lines.insert(0, line);
+            }
+        }
+
+        let mut result = Err(Error::NoLineAvailable);
+        for line in lines {
+            result = self
+                .upload_on_line(line, video, total_size, sx.clone(), part_name.clone())
+                .await;
+            match &result {
+                Err(e) if e.is_line_failure() => {
+                    log::warn!("Upload on line {} failed: {e:#}", line.name())
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// 使用指定线路上传单个分P
+    pub async fn upload_video_part_on<P>(
+        &self,
+        line: &UploadLine,
+        video: P,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+        part_name: Option<String>,
+    ) -> Result<VideoPart>
+    where
+        P: AsRef<Path>,
+    {
+        let video = video.as_ref();
+        let upload = self.upload_on_line(line, video, total_size, sx, part_name);
+        self.with_md5(video, total_size, upload).await
+    }
+
+    async fn upload_on_line(
+        &self,
+        line: &UploadLine,
+        video: &Path,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+        part_name: Option<String>,
+    ) -> Result<VideoPart> {
+        let part = line.upload(self, video, total_size, sx).await?;
+        Ok(Self::name_part(part, line, part_name))
+    }
+
+    /// 在上传的同时计算整个文件的 MD5
+    ///
+    /// 上传失败时仍会等待 MD5 计算完成并缓存结果，重试上传同一文件时不再重新读取。
+    async fn with_md5<F>(&self, video: &Path, total_size: usize, upload: F) -> Result<VideoPart>
+    where
+        F: Future<Output = Result<VideoPart>>,
+    {
+        let (part, md5) = tokio::join!(upload, self.file_md5(video, total_size));
+        let mut part = part?;
+        part.md5 = Some(md5?);
+        Ok(part)
+    }
+
+    /// 计算文件的 MD5，文件未修改时使用缓存的结果
+    async fn file_md5(&self, video: &Path, total_size: usize) -> Result<String> {
+        let modified = upos::modified_time(video).await?;
+        if let Some((cached_modified, cached_size, md5)) = self.md5_cache.lock().unwrap().get(video)
+        {
+            if *cached_modified == modified && *cached_size == total_size {
+                return Ok(md5.clone());
+            }
+        }
+        let md5 = chunk::file_md5(video).await?;
+        self.md5_cache
+            .lock()
+            .unwrap()
+            .insert(video.to_path_buf(), (modified, total_size, md5.clone()));
+        Ok(md5)
+    }
+
+    /// 从任意数据源上传单个分P，如 ffmpeg 的输出或下载流
+    ///
+    /// `reader` 需恰好提供 `total_size` 字节。数据只能读取一次，因此仅使用首选线路上传，
+    /// 不支持线路切换与断点续传。
+    pub async fn upload_video_part_from<R>(
+        &self,
+        reader: R,
+        file_name: &str,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+        part_name: Option<String>,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.upload_video_part_from_on(
+            self.upload_line(),
+            reader,
+            file_name,
+            total_size,
+            sx,
+            part_name,
+        )
+        .await
+    }
+
+    /// 使用指定线路从任意数据源上传单个分P
+    pub async fn upload_video_part_from_on<R>(
+        &self,
+        line: &UploadLine,
+        reader: R,
+        file_name: &str,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+        part_name: Option<String>,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let part = line
+            .upload_reader(self, reader, file_name, total_size, sx)
+            .await?;
+        Ok(Self::name_part(part, line, part_name))
+    }
+
+    fn name_part(mut part: VideoPart, line: &UploadLine, part_name: Option<String>) -> VideoPart {
         if let Some(name) = part_name {
             part.title = Some(name);
         }
-        Ok(part)
+        part.line = Some(line.name().to_string());
+        part
     }
 
     pub fn upload_line(&self) -> &UploadLine {
-        &self.line
+        &self.lines[0]
     }
 
     /// 查看现有投稿信息
-    pub async fn get_video(&self, id: &VideoId) -> anyhow::Result<EditVideo> {
+    pub async fn get_video(&self, id: &VideoId) -> Result<EditVideo> {
         let id = match id {
             VideoId::AId(aid) => format!("aid={aid}"),
             VideoId::BVId(bvid) => format!("bvid={bvid}"),
         };
 
-        let ret: serde_json::Value = self
+        #[derive(Deserialize)]
+        struct ArchiveView {
+            archive: EditVideo,
+            videos: Vec<EditVideoPart>,
+        }
+
+        let ret: Value = self
             .client
             .get(format!(
-                "https://member.bilibili.com/x/client/archive/view?{id}"
+                "{}/x/client/archive/view?{id}",
+                self.config.member_url
             ))
             .send()
             .await?
             .json()
             .await?;
+        Error::check(&ret)?;
+
+        let view: ArchiveView = serde_json::from_value(ret["data"].clone())?;
+        let mut video = view.archive;
+        video.videos = view.videos;
+        Ok(video)
+    }
+
+    /// 查看自己的稿件列表，`status` 为空时列出所有状态的稿件
+    pub async fn list_archives(
+        &self,
+        status: &[ArchiveStatus],
+        page: u32,
+        page_size: u32,
+    ) -> Result<ArchivePage> {
+        let status = if status.is_empty() {
+            &[
+                ArchiveStatus::Reviewing,
+                ArchiveStatus::Published,
+                ArchiveStatus::Rejected,
+            ][..]
+        } else {
+            status
+        };
+        let status = status
+            .iter()
+            .map(ArchiveStatus::as_query)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        #[derive(Deserialize)]
+        struct ArcAudit {
+            #[serde(rename = "Archive")]
+            archive: ArchiveItem,
+        }
+
+        #[derive(Deserialize)]
+        struct Page {
+            pn: u32,
+            ps: u32,
+            count: u64,
+        }
 
-        if ret["code"] != 0 {
-            bail!("{:?}", ret);
+        #[derive(Deserialize)]
+        struct Archives {
+            // 没有稿件时为 null
+            arc_audits: Option<Vec<ArcAudit>>,
+            page: Page,
         }
 
-        let ret = &ret["data"];
-        let data = &ret["archive"];
-        let video = EditVideo {
-            aid: data["aid"].as_u64().unwrap(),
-            copyright: data["copyright"].as_i64().unwrap(),
-            source: data["source"].as_str().unwrap().into(),
-            tid: data["tid"].as_u64().unwrap() as u16,
-            cover: data["cover"].as_str().unwrap().into(),
-            title: data["title"].as_str().unwrap().into(),
-            desc_format_id: data["desc_format_id"].as_i64().unwrap(),
-            desc: data["desc"].as_str().unwrap().into(),
-            dynamic: data["dynamic"].as_str().unwrap().into(),
-            tag: data["tag"].as_str().unwrap().into(),
-            videos: ret["videos"]
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|video| EditVideoPart {
-                    title: Some(video["title"].as_str().unwrap().into()),
-                    filename: video["filename"].as_str().unwrap().into(),
-                    desc: video["desc"].as_str().unwrap().into(),
-                    cid: Some(video["cid"].as_u64().unwrap()),
-                    duration: video["duration"].as_u64().unwrap(),
-                })
+        let ret: Value = self
+            .client
+            .get(format!("{}/x/web/archives", self.config.member_url))
+            .query(&[
+                ("status", status),
+                ("pn", page.to_string()),
+                ("ps", page_size.to_string()),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+        Error::check(&ret)?;
+
+        let archives: Archives = serde_json::from_value(ret["data"].clone())?;
+        Ok(ArchivePage {
+            archives: archives
+                .arc_audits
+                .unwrap_or_default()
+                .into_iter()
+                .map(|audit| audit.archive)
                 .collect(),
-            display_time: data["dtime"].as_i64(),
-        };
-        Ok(video)
+            page: archives.page.pn,
+            page_size: archives.page.ps,
+            total: archives.page.count,
+        })
     }
 
     /// 投稿
-    pub async fn submit(&self, form: &Video) -> anyhow::Result<()> {
+    pub async fn submit(&self, form: &Video) -> Result<SubmitResult> {
         let ret: serde_json::Value = self
             .client
             .post(format!(
-                "https://member.bilibili.com/x/vu/client/add?access_key={}",
-                self.credential.token_info.access_token
+                "{}/x/vu/client/add?access_key={}",
+                self.config.member_url,
+                self.credential.access_token()?
             ))
             .json(&form)
             .send()
             .await?
             .json()
             .await?;
-        if ret["code"] == 0 {
-            // Ok(ret)
-            Ok(())
-        } else {
-            bail!("{}", ret)
-        }
+        Error::check(&ret)?;
+        Ok(serde_json::from_value(ret["data"].clone())?)
     }
 
-    pub async fn submit_by_app(&self, studio: &Video) -> anyhow::Result<()> {
+    /// 以客户端接口投稿
+    pub async fn submit_by_app(&self, studio: &Video) -> Result<SubmitResult> {
         let payload = {
             let mut payload = json!({
-                "access_key": self.credential.token_info.access_token,
+                "access_key": self.credential.access_token()?,
                 "appkey": "4409e2ce8ffd12b8",
                 "build": 7800300,
                 "c_locale": "zh-Hans_CN",
@@ -223,44 +570,58 @@ impl Client {
             payload
         };
 
-        let ret: Value = reqwest::Client::builder()
+        let ret: Value = self
+            .config
+            .http_builder()?
             .user_agent("Mozilla/5.0 BiliDroid/7.80.0 (bbcallen@gmail.com) os/android model/MI 6 mobi_app/android build/7800300 channel/bili innerVer/7800310 osVer/13 network/2")
-            .timeout(Duration::new(60, 0))
             .build()?
-            .post("https://member.bilibili.com/x/vu/app/add")
+            .post(format!("{}/x/vu/app/add", self.config.member_url))
             .query(&payload)
             .json(studio)
             .send()
             .await?
             .json()
             .await?;
-        log::info!("{:?}", ret);
-        if ret["code"] == 0 {
-            Ok(())
-        } else {
-            anyhow::bail!("{ret:?}")
-        }
+        log::debug!("{:?}", ret);
+        Error::check(&ret)?;
+        Ok(serde_json::from_value(ret["data"].clone())?)
     }
 
-    /// 修改现有投稿
-    pub async fn submit_edit(&self, form: &EditVideo) -> anyhow::Result<()> {
-        let ret: serde_json::Value = self
+    /// 以网页端接口投稿，只需要 Cookie
+    pub async fn submit_by_web(&self, studio: &Video) -> Result<SubmitResult> {
+        let ret: Value = self
             .client
-            .post(format!(
-                "https://member.bilibili.com/x/vu/client/edit?access_key={}",
-                self.credential.token_info.access_token
-            ))
-            .json(&form)
+            .post(format!("{}/x/vu/web/add/v3", self.config.member_url))
+            .query(&[("csrf", self.credential.csrf()?)])
+            .json(studio)
             .send()
             .await?
             .json()
             .await?;
-        if ret["code"] == 0 {
-            // Ok(ret)
-            Ok(())
+        Error::check(&ret)?;
+        Ok(serde_json::from_value(ret["data"].clone())?)
+    }
+
+    /// 修改现有投稿，凭据中没有 Access Token 时使用网页端接口
+    pub async fn submit_edit(&self, form: &EditVideo) -> Result<()> {
+        let request = if self.credential.has_access_token() {
+            self.client.post(format!(
+                "{}/x/vu/client/edit?access_key={}",
+                self.config.member_url,
+                self.credential.access_token()?
+            ))
         } else {
-            bail!("{}", ret)
-        }
+            self.client
+                .post(format!("{}/x/vu/web/edit", self.config.member_url))
+                .query(&[("csrf", self.credential.csrf()?)])
+        };
+        let ret: serde_json::Value = request.json(&form).send().await?.json().await?;
+        Error::check(&ret)
+    }
+
+    /// 凭据中是否包含客户端接口所需的 Access Token
+    pub fn has_access_token(&self) -> bool {
+        self.credential.has_access_token()
     }
 
     /// 修改投稿分段章节
@@ -270,12 +631,12 @@ impl Client {
         cid: u64,
         cards: Vec<VideoCardItem>,
         permanent: bool,
-    ) -> anyhow::Result<()> {
-        let csrf = self.credential.cookie_info.get("bili_jct").unwrap();
+    ) -> Result<()> {
+        let csrf = self.credential.csrf()?;
         let cards = serde_json::to_string(&cards)?;
         let response: serde_json::Value = self
             .client
-            .post("https://member.bilibili.com/x/web/card/submit")
+            .post(format!("{}/x/web/card/submit", self.config.member_url))
             .form(&json!({
                 "aid": aid,
                 "cid": cid,
@@ -288,11 +649,6 @@ impl Client {
             .await?
             .json()
             .await?;
-
-        if response["code"] == 0 {
-            Ok(())
-        } else {
-            bail!("{}", response)
-        }
+        Error::check(&response)
     }
 }
@@ -0,0 +1,113 @@
+use crate::constants::{CONCURRENCY, USER_AGENT};
+use crate::error::Result;
+use crate::limit::{ChunkBudget, RateLimiter};
+use std::time::Duration;
+
+/// 客户端配置
+///
+/// 默认指向 B 站的正式接口，可以替换为本地服务用于测试。
+/// User-Agent 与并发数的默认值取自 [`crate::constants`] 中的全局设置。
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// 创作中心接口，如 `https://member.bilibili.com`
+    pub member_url: String,
+    /// 登录接口，如 `https://passport.bilibili.com`
+    pub passport_url: String,
+    /// TV 端扫码确认接口，如 `https://passport.snm0516.aisee.tv`
+    pub tv_passport_url: String,
+    /// 主站接口，如 `https://api.bilibili.com`
+    pub api_url: String,
+    /// 替换上传线路返回的上传地址，为空时使用线路返回的地址
+    pub upos_url: Option<String>,
+
+    /// 请求使用的 User-Agent
+    pub user_agent: String,
+    /// 单个分P同时上传的分块数
+    pub concurrency: usize,
+    /// 单个分P同时在内存中的分块数据上限，单位为字节，至少保留一个分块
+    pub max_in_flight: usize,
+    /// 接口请求的超时时间
+    pub timeout: Duration,
+    /// 上传分块的超时时间
+    pub upload_timeout: Duration,
+    /// 上传分块失败时的最大重试次数
+    pub max_retries: u32,
+    /// 重试的最短间隔
+    pub min_retry_interval: Duration,
+    /// 重试的最长间隔
+    pub max_retry_interval: Duration,
+    /// 代理地址，如 `http://127.0.0.1:7890`
+    pub proxy: Option<String>,
+    /// 扫码登录的总超时时间，包括二维码过期后重新生成的时间，为空时一直等待
+    pub login_timeout: Option<Duration>,
+    /// 轮询扫码结果的间隔
+    pub qrcode_poll_interval: Duration,
+
+    /// 所有分P共享的上传限速，复制的配置共享同一个限速器
+    pub rate_limiter: RateLimiter,
+    /// 单个分P的上传速率上限，单位为字节每秒
+    pub part_rate_limit: Option<u64>,
+    /// 所有分P共享的同时上传分块数上限，复制的配置共享同一个上限
+    pub chunk_budget: ChunkBudget,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            member_url: "https://member.bilibili.com".to_string(),
+            passport_url: "https://passport.bilibili.com".to_string(),
+            tv_passport_url: "https://passport.snm0516.aisee.tv".to_string(),
+            api_url: "https://api.bilibili.com".to_string(),
+            upos_url: None,
+            user_agent: USER_AGENT.read().clone(),
+            concurrency: *CONCURRENCY.read(),
+            max_in_flight: 128 * 1024 * 1024,
+            timeout: Duration::from_secs(60),
+            upload_timeout: Duration::from_secs(300),
+            max_retries: 3,
+            min_retry_interval: Duration::from_secs(1),
+            max_retry_interval: Duration::from_secs(30),
+            proxy: None,
+            login_timeout: Some(Duration::from_secs(300)),
+            qrcode_poll_interval: Duration::from_secs(1),
+            rate_limiter: RateLimiter::default(),
+            part_rate_limit: None,
+            chunk_budget: ChunkBudget::default(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// 将上传线路返回的地址转换为实际请求的地址
+    ///
+    /// 省略协议的地址补全为 https，设置了 `upos_url` 时替换其协议和主机部分。
+    pub fn upload_url(&self, url: &str) -> String {
+        match &self.upos_url {
+            Some(base) => {
+                let path = url
+                    .strip_prefix("//")
+                    .or_else(|| url.split_once("://").map(|(_, rest)| rest))
+                    .map(|rest| rest.find('/').map_or("", |i| &rest[i..]))
+                    .unwrap_or(url);
+                format!("{}{path}", base.trim_end_matches('/'))
+            }
+            None if url.starts_with("//") => format!("https:{url}"),
+            None => url.to_string(),
+        }
+    }
+
+    /// 按配置设置 User-Agent、超时与代理的 HTTP 客户端
+    pub(crate) fn http_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .timeout(self.timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(builder)
+    }
+
+    pub(crate) fn http_client(&self) -> Result<reqwest::Client> {
+        Ok(self.http_builder()?.build()?)
+    }
+}
@@ -8,12 +8,16 @@ lazy_static! {
     pub(crate) static ref CONCURRENCY: RwLock<usize> = RwLock::new(3);
 }
 
-/// 设置所有请求中使用的 User-Agent
+/// 设置请求中使用的默认 User-Agent
+///
+/// 仅影响之后创建的 [`crate::ClientConfig`]
 pub fn set_useragent(user_agent: String) {
     *USER_AGENT.write() = user_agent;
 }
 
-/// 设置分P上传的并发数
+/// 设置分P上传的默认并发数
+///
+/// 仅影响之后创建的 [`crate::ClientConfig`]
 pub fn set_concurrency(concurrency: usize) {
     if concurrency > 0 {
         *CONCURRENCY.write() = concurrency;
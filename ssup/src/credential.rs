@@ -1,10 +1,19 @@
-use anyhow::bail;
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::uploader::utils;
 use cookie::Cookie;
 use md5::{Digest, Md5};
+use reqwest::header::SET_COOKIE;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
 use std::str::FromStr;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 短信登录使用 Android 客户端的 appkey
+const SMS_APPKEY: &str = "783bbb7264451d82";
+const SMS_APPSEC: &str = "2653583c8873dea268ab9386918b1d65";
 
 /// 存储用户的登录信息
 #[derive(Serialize, Deserialize, Debug)]
@@ -12,12 +21,15 @@ pub struct Credential {
     #[serde(default)]
     login_time: u64,
     pub(crate) cookie_info: CookieInfo,
+    #[serde(default)]
     pub(crate) sso: Vec<String>,
-    pub(crate) token_info: TokenInfo,
+    /// 网页端扫码登录时只有 Cookie，没有 Token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) token_info: Option<TokenInfo>,
 }
 
 impl Credential {
-    pub async fn get_qrcode() -> anyhow::Result<Value> {
+    pub async fn get_qrcode(config: &ClientConfig) -> Result<Value> {
         let mut form = json!({
             "appkey": "4409e2ce8ffd12b8",
             "local_id": "0",
@@ -26,8 +38,12 @@ impl Credential {
         let urlencoded = serde_urlencoded::to_string(&form)?;
         let sign = Credential::sign(&urlencoded, "59b43e04ad6965f34319062b478f83dd");
         form["sign"] = Value::from(sign);
-        Ok(reqwest::Client::new()
-            .post("http://passport.bilibili.com/x/passport-tv-login/qrcode/auth_code")
+        Ok(config
+            .http_client()?
+            .post(format!(
+                "{}/x/passport-tv-login/qrcode/auth_code",
+                config.passport_url
+            ))
             .form(&form)
             .send()
             .await?
@@ -41,7 +57,21 @@ impl Credential {
         format!("{:x}", hasher.finalize())
     }
 
-    pub async fn from_qrcode(value: Value) -> anyhow::Result<Self> {
+    pub async fn from_qrcode(config: &ClientConfig, value: Value) -> Result<Self> {
+        Self::from_qrcode_with(config, value, |_| {}).await
+    }
+
+    /// 轮询二维码的登录结果，状态变化时调用 `on_status`
+    ///
+    /// 二维码过期时返回 [`Error::QrcodeExpired`]。
+    pub async fn from_qrcode_with<F>(
+        config: &ClientConfig,
+        value: Value,
+        mut on_status: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(QrcodeStatus),
+    {
         let mut form = json!({
             "appkey": "4409e2ce8ffd12b8",
             "local_id": "0",
@@ -51,12 +81,12 @@ impl Credential {
         let urlencoded = serde_urlencoded::to_string(&form)?;
         let sign = Credential::sign(&urlencoded, "59b43e04ad6965f34319062b478f83dd");
         form["sign"] = Value::from(sign);
+        let client = config.http_client()?;
+        let url = format!("{}/x/passport-tv-login/qrcode/poll", config.passport_url);
+        let mut scanned = false;
         loop {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-            let res: ResponseData = reqwest::Client::new()
-                .post("http://passport.bilibili.com/x/passport-tv-login/qrcode/poll")
-                .form(&form)
-                .send()
+            tokio::time::sleep(config.qrcode_poll_interval).await;
+            let res: ResponseData = utils::send(config, || client.post(&url).form(&form))
                 .await?
                 .json()
                 .await?;
@@ -75,42 +105,286 @@ impl Credential {
                     return Ok(info);
                 }
                 ResponseData { code: 86039, .. } => {
-                    // 二维码尚未确认;
-                    // form["ts"] = Value::from(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+                    // 二维码尚未扫描
+                }
+                ResponseData { code: 86090, .. } => {
+                    // 二维码已扫描，等待确认
+                    if !scanned {
+                        scanned = true;
+                        on_status(QrcodeStatus::Scanned);
+                    }
+                }
+                ResponseData { code: 86038, .. } => return Err(Error::QrcodeExpired),
+                ResponseData { code: 0, .. } => {
+                    return Err(Error::UnexpectedResponse(format!("{res:?}")));
+                }
+                _ => return Err(Error::from_code(res.code as i64, res.message)),
+            }
+        }
+    }
+
+    /// 扫码登录，二维码过期时自动重新生成
+    ///
+    /// 每次生成二维码时都会以 [`QrcodeStatus::Generated`] 通知调用方展示。
+    /// 超过 [`ClientConfig::login_timeout`] 时返回 [`Error::LoginTimeout`]，
+    /// 丢弃返回的 future 即可取消登录。
+    pub async fn login_by_qrcode<F>(config: &ClientConfig, on_status: F) -> Result<Self>
+    where
+        F: FnMut(QrcodeStatus),
+    {
+        Self::with_login_timeout(config, Self::login_by_qrcode_inner(config, on_status)).await
+    }
+
+    async fn with_login_timeout<Fut>(config: &ClientConfig, login: Fut) -> Result<Self>
+    where
+        Fut: Future<Output = Result<Self>>,
+    {
+        match config.login_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, login)
+                .await
+                .map_err(|_| Error::LoginTimeout(timeout))?,
+            None => login.await,
+        }
+    }
+
+    async fn login_by_qrcode_inner<F>(config: &ClientConfig, mut on_status: F) -> Result<Self>
+    where
+        F: FnMut(QrcodeStatus),
+    {
+        loop {
+            let qrcode = Self::get_qrcode(config).await?;
+            let url = qrcode["data"]["url"]
+                .as_str()
+                .ok_or_else(|| Error::UnexpectedResponse(qrcode.to_string()))?;
+            on_status(QrcodeStatus::Generated {
+                url: url.to_string(),
+            });
+            match Self::from_qrcode_with(config, qrcode, &mut on_status).await {
+                Err(Error::QrcodeExpired) => on_status(QrcodeStatus::Expired),
+                result => return result,
+            }
+        }
+    }
+
+    /// 获取网页端的登录二维码
+    pub async fn get_web_qrcode(config: &ClientConfig) -> Result<Value> {
+        let response: Value = config
+            .http_client()?
+            .get(format!(
+                "{}/x/passport-login/web/qrcode/generate",
+                config.passport_url
+            ))
+            .send()
+            .await?
+            .json()
+            .await?;
+        Error::check(&response)?;
+        Ok(response)
+    }
+
+    /// 轮询网页端二维码的登录结果，登录后返回只包含 Cookie 的凭据
+    ///
+    /// 二维码过期时返回 [`Error::QrcodeExpired`]。
+    pub async fn from_web_qrcode_with<F>(
+        config: &ClientConfig,
+        value: Value,
+        mut on_status: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(QrcodeStatus),
+    {
+        let qrcode_key = value["data"]["qrcode_key"]
+            .as_str()
+            .ok_or_else(|| Error::UnexpectedResponse(value.to_string()))?;
+        let client = config.http_client()?;
+        let url = format!("{}/x/passport-login/web/qrcode/poll", config.passport_url);
+        let mut scanned = false;
+        loop {
+            tokio::time::sleep(config.qrcode_poll_interval).await;
+            let response = utils::send(config, || {
+                client.get(&url).query(&[("qrcode_key", qrcode_key)])
+            })
+            .await?;
+            let cookies: Vec<CookieEntry> = response
+                .headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .filter_map(|value| Cookie::parse(value).ok())
+                .map(|cookie| CookieEntry {
+                    name: cookie.name().to_string(),
+                    value: cookie.value().to_string(),
+                })
+                .collect();
+            let response: Value = response.json().await?;
+            Error::check(&response)?;
+            match response["data"]["code"].as_i64() {
+                Some(0) => {
+                    let cookie_info = CookieInfo::new(cookies);
+                    cookie_info.validate()?;
+                    return Ok(Self {
+                        login_time: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        cookie_info,
+                        sso: Vec::new(),
+                        token_info: None,
+                    });
+                }
+                Some(86101) => {
+                    // 二维码尚未扫描
+                }
+                Some(86090) => {
+                    if !scanned {
+                        scanned = true;
+                        on_status(QrcodeStatus::Scanned);
+                    }
                 }
-                _ => {
-                    bail!("{:#?}", res)
+                Some(86038) => return Err(Error::QrcodeExpired),
+                Some(code) => {
+                    let message = response["data"]["message"].as_str().unwrap_or_default();
+                    return Err(Error::from_code(code, message));
                 }
+                None => return Err(Error::UnexpectedResponse(response.to_string())),
+            }
+        }
+    }
+
+    /// 网页端扫码登录，二维码过期时自动重新生成
+    ///
+    /// 不经过 TV 端的接口，因此不受 TV 端登录的限制。得到的凭据只包含 Cookie，没有 Token，
+    /// 无法刷新，投稿与修改稿件时使用网页端的接口。
+    pub async fn login_by_web_qrcode<F>(config: &ClientConfig, mut on_status: F) -> Result<Self>
+    where
+        F: FnMut(QrcodeStatus),
+    {
+        let login = async {
+            loop {
+                let qrcode = Self::get_web_qrcode(config).await?;
+                let url = qrcode["data"]["url"]
+                    .as_str()
+                    .ok_or_else(|| Error::UnexpectedResponse(qrcode.to_string()))?;
+                on_status(QrcodeStatus::Generated {
+                    url: url.to_string(),
+                });
+                match Self::from_web_qrcode_with(config, qrcode, &mut on_status).await {
+                    Err(Error::QrcodeExpired) => on_status(QrcodeStatus::Expired),
+                    result => return result,
+                }
+            }
+        };
+        Self::with_login_timeout(config, login).await
+    }
+
+    /// 向手机号 `tel` 发送登录验证码，`cid` 为国际区号，如中国大陆为 86
+    pub async fn send_sms(config: &ClientConfig, cid: u32, tel: &str) -> Result<SmsCode> {
+        let mut form = json!({
+            "appkey": SMS_APPKEY,
+            "cid": cid,
+            "tel": tel,
+            "ts": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+        });
+        let urlencoded = serde_urlencoded::to_string(&form)?;
+        let sign = Credential::sign(&urlencoded, SMS_APPSEC);
+        form["sign"] = Value::from(sign);
+        let response: Value = config
+            .http_client()?
+            .post(format!("{}/x/passport-login/sms/send", config.passport_url))
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Error::check(&response)?;
+        match response["data"]["captcha_key"].as_str() {
+            Some(captcha_key) if !captcha_key.is_empty() => Ok(SmsCode {
+                cid,
+                tel: tel.to_string(),
+                captcha_key: captcha_key.to_string(),
+            }),
+            // 需要先通过人机验证，无法在命令行中完成
+            _ => Err(Error::UnexpectedResponse(format!(
+                "captcha required: {}",
+                response["data"]["recaptcha_url"]
+            ))),
+        }
+    }
+
+    /// 以收到的短信验证码登录
+    pub async fn from_sms(config: &ClientConfig, sms: &SmsCode, code: &str) -> Result<Self> {
+        let mut form = json!({
+            "appkey": SMS_APPKEY,
+            "captcha_key": sms.captcha_key,
+            "cid": sms.cid,
+            "code": code,
+            "tel": sms.tel,
+            "ts": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+        });
+        let urlencoded = serde_urlencoded::to_string(&form)?;
+        let sign = Credential::sign(&urlencoded, SMS_APPSEC);
+        form["sign"] = Value::from(sign);
+        let res: ResponseData = config
+            .http_client()?
+            .post(format!(
+                "{}/x/passport-login/login/sms",
+                config.passport_url
+            ))
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+        match res {
+            ResponseData {
+                code: 0,
+                data: ResponseValue::Login(mut info),
+                ..
+            } => {
+                info.login_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                Ok(info)
             }
+            ResponseData { code: 0, .. } => Err(Error::UnexpectedResponse(format!("{res:?}"))),
+            _ => Err(Error::from_code(res.code as i64, res.message)),
         }
     }
 
-    pub async fn get_nickname(&self) -> anyhow::Result<String> {
-        let response: ResponseData = reqwest::Client::new()
-            .get("https://api.bilibili.com/x/web-interface/nav")
+    pub async fn get_nickname(&self, config: &ClientConfig) -> Result<String> {
+        let response: ResponseData = config
+            .http_client()?
+            .get(format!("{}/x/web-interface/nav", config.api_url))
             .header("Cookie", self.cookie_info.to_string())
             .send()
             .await?
             .json()
             .await?;
         if response.code != 0 {
-            bail!("{:#?}", response)
+            return Err(Error::from_code(response.code as i64, response.message));
         }
-        match response.data {
-            ResponseValue::Value(data) => Ok(data["uname"].as_str().unwrap().to_string()),
-            _ => unreachable!(),
+        match &response.data {
+            ResponseValue::Value(data) if data["uname"].is_string() => {
+                Ok(data["uname"].as_str().unwrap_or_default().to_string())
+            }
+            _ => Err(Error::UnexpectedResponse(format!("{response:?}"))),
         }
     }
 
-    pub async fn from_cookies(cookies: &CookieInfo) -> anyhow::Result<Self> {
-        let qrcode = Self::get_qrcode().await?;
+    pub async fn from_cookies(config: &ClientConfig, cookies: &CookieInfo) -> Result<Self> {
+        let qrcode = Self::get_qrcode(config).await?;
         let form = json!({
             "auth_code": qrcode["data"]["auth_code"],
-            "csrf": cookies.get("bili_jct").unwrap(),
+            "csrf": cookies.csrf()?,
             "scanning_type": 3,
         });
-        let response: ResponseData = reqwest::Client::new()
-            .post("https://passport.snm0516.aisee.tv/x/passport-tv-login/h5/qrcode/confirm")
+        let response: ResponseData = config
+            .http_client()?
+            .post(format!(
+                "{}/x/passport-tv-login/h5/qrcode/confirm",
+                config.tv_passport_url
+            ))
             .header("Cookie", cookies.to_string())
             .form(&form)
             .send()
@@ -118,36 +392,147 @@ impl Credential {
             .json()
             .await?;
         if response.code != 0 {
-            bail!("{:#?}", response)
+            return Err(Error::from_code(response.code as i64, response.message));
+        }
+
+        Self::from_qrcode(config, qrcode).await
+    }
+
+    pub(crate) fn csrf(&self) -> Result<&str> {
+        self.cookie_info.csrf()
+    }
+
+    /// 是否包含客户端接口所需的 Access Token
+    pub fn has_access_token(&self) -> bool {
+        self.token_info.is_some()
+    }
+
+    pub(crate) fn access_token(&self) -> Result<&str> {
+        self.token_info
+            .as_ref()
+            .map(|token| token.access_token.as_str())
+            .ok_or(Error::NoAccessToken)
+    }
+
+    /// 登录用户的 mid
+    pub fn mid(&self) -> u64 {
+        match &self.token_info {
+            Some(token) => token.mid,
+            None => self
+                .cookie_info
+                .get("DedeUserID")
+                .and_then(|mid| mid.parse().ok())
+                .unwrap_or_default(),
         }
+    }
+
+    /// 登录时间，为 Unix 时间戳
+    pub fn login_time(&self) -> u64 {
+        self.login_time
+    }
 
-        Self::from_qrcode(qrcode).await
+    /// Token 的过期时间，为 Unix 时间戳，没有 Token 时为 0
+    pub fn expires_at(&self) -> u64 {
+        match &self.token_info {
+            Some(token) => self.login_time + token.expires_in,
+            None => 0,
+        }
     }
 
-    fn need_refresh(&self) -> bool {
+    /// Token 是否即将过期，需要刷新，没有 Token 时无法刷新
+    pub fn need_refresh(&self) -> bool {
         // Token过期前30天内重新获取
-        (self.login_time + self.token_info.expires_in as u64)
-            < (SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                + 30 * 86400)
-    }
-
-    pub async fn refresh(&mut self, force: bool) -> anyhow::Result<bool> {
-        if force || self.need_refresh() {
-            let refreshed = Credential::from_cookies(&self.cookie_info).await?;
-            self.login_time = refreshed.login_time;
-            self.cookie_info = refreshed.cookie_info;
-            self.token_info = refreshed.token_info;
-            self.sso = refreshed.sso;
-            Ok(true)
-        } else {
-            Ok(false)
+        self.has_access_token()
+            && self.expires_at()
+                < (SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    + 30 * 86400)
+    }
+
+    /// 使用 `refresh_token` 换取新的登录凭据
+    pub async fn refresh_token(&self, config: &ClientConfig) -> Result<Self> {
+        let token = self.token_info.as_ref().ok_or(Error::NoAccessToken)?;
+        let mut form = json!({
+            "access_key": token.access_token,
+            "appkey": "4409e2ce8ffd12b8",
+            "refresh_token": token.refresh_token,
+            "ts": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+        });
+        let urlencoded = serde_urlencoded::to_string(&form)?;
+        let sign = Credential::sign(&urlencoded, "59b43e04ad6965f34319062b478f83dd");
+        form["sign"] = Value::from(sign);
+        let res: ResponseData = config
+            .http_client()?
+            .post(format!(
+                "{}/x/passport-login/oauth2/refresh_token",
+                config.passport_url
+            ))
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+        match res {
+            ResponseData {
+                code: 0,
+                data: ResponseValue::Login(mut info),
+                ..
+            } => {
+                info.login_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                Ok(info)
+            }
+            ResponseData { code: 0, .. } => Err(Error::UnexpectedResponse(format!("{res:?}"))),
+            _ => Err(Error::from_code(res.code as i64, res.message)),
+        }
+    }
+
+    /// 在凭据即将过期或 `force` 时刷新凭据，返回是否进行了刷新
+    ///
+    /// 优先使用 `refresh_token` 刷新，失败时以 Cookie 重新登录。没有 Token 的凭据无法刷新，
+    /// `force` 时返回 [`Error::NoAccessToken`]。
+    pub async fn refresh(&mut self, config: &ClientConfig, force: bool) -> Result<bool> {
+        if !force && !self.need_refresh() {
+            return Ok(false);
         }
+        if !self.has_access_token() {
+            return Err(Error::NoAccessToken);
+        }
+        let refreshed = match self.refresh_token(config).await {
+            Ok(refreshed) => refreshed,
+            Err(e) => {
+                log::warn!("Failed to refresh token, logging in with cookies: {e}");
+                Credential::from_cookies(config, &self.cookie_info).await?
+            }
+        };
+        *self = refreshed;
+        Ok(true)
     }
 }
 
+/// 已发送的短信验证码，用于 [`Credential::from_sms`]
+#[derive(Debug, Clone)]
+pub struct SmsCode {
+    cid: u32,
+    tel: String,
+    captcha_key: String,
+}
+
+/// 扫码登录的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrcodeStatus {
+    /// 生成了新的二维码，`url` 为二维码的内容
+    Generated { url: String },
+    /// 二维码已被扫描，等待在手机上确认
+    Scanned,
+    /// 二维码已过期，即将重新生成
+    Expired,
+}
+
 /// 存储 Cookie 信息
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CookieInfo {
@@ -165,15 +550,119 @@ impl CookieInfo {
             .find(|entry| entry.name == key)
             .map(|entry| entry.value.as_str())
     }
+
+    /// 解析浏览器导出的 Cookie，格式按内容自动识别
+    ///
+    /// 支持 Netscape 格式的 cookies.txt、EditThisCookie 导出的 JSON
+    /// 以及 `Cookie:` 请求头，前两种格式只保留 B 站域名下的 Cookie。
+    pub fn parse(content: &str) -> Result<Self> {
+        let content = content.trim();
+        if content.starts_with('[') {
+            Self::from_json(content)
+        } else if content.lines().any(|line| line.split('\t').count() == 7) {
+            Ok(Self::from_netscape(content))
+        } else {
+            Self::from_header(content)
+        }
+    }
+
+    /// 解析 Netscape 格式的 cookies.txt
+    pub fn from_netscape(content: &str) -> Self {
+        let cookies = content
+            .lines()
+            .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let fields: Vec<_> = line.trim_end_matches('\r').split('\t').collect();
+                match fields[..] {
+                    [domain, _, _, _, _, name, value] if is_bilibili_domain(domain) => {
+                        Some(CookieEntry {
+                            name: name.to_string(),
+                            value: value.to_string(),
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        Self { cookies }
+    }
+
+    /// 解析 EditThisCookie 导出的 JSON
+    pub fn from_json(content: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct ExportedCookie {
+            #[serde(default)]
+            domain: Option<String>,
+            name: String,
+            value: String,
+        }
+
+        let exported: Vec<ExportedCookie> = serde_json::from_str(content)?;
+        let cookies = exported
+            .into_iter()
+            .filter(|cookie| cookie.domain.as_deref().is_none_or(is_bilibili_domain))
+            .map(|cookie| CookieEntry {
+                name: cookie.name,
+                value: cookie.value,
+            })
+            .collect();
+        Ok(Self { cookies })
+    }
+
+    /// 解析 `Cookie:` 请求头，可省略 `Cookie:` 前缀
+    pub fn from_header(content: &str) -> Result<Self> {
+        let content = content.trim();
+        let content = match content.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("cookie") => value,
+            _ => content,
+        };
+        let cookies = content
+            .split(';')
+            .map(str::trim)
+            .filter(|cookie| !cookie.is_empty())
+            .map(CookieEntry::from_str)
+            .collect::<Result<_>>()?;
+        Ok(Self { cookies })
+    }
+
+    /// 检查登录所需的 `bili_jct`、`SESSDATA` 与 `DedeUserID` 是否齐全
+    pub fn validate(&self) -> Result<()> {
+        let missing: Vec<_> = ["bili_jct", "SESSDATA", "DedeUserID"]
+            .into_iter()
+            .filter(|name| self.get(name).is_none())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MissingCookies(missing.join(", ")))
+        }
+    }
+
+    /// 获取用作 CSRF Token 的 `bili_jct`
+    pub(crate) fn csrf(&self) -> Result<&str> {
+        self.get("bili_jct")
+            .ok_or_else(|| Error::CredentialExpired {
+                code: -111,
+                message: "bili_jct not found in cookies".to_string(),
+            })
+    }
 }
 
-impl ToString for CookieInfo {
-    fn to_string(&self) -> String {
-        self.cookies
+fn is_bilibili_domain(domain: &str) -> bool {
+    let domain = domain.trim_start_matches('.');
+    domain == "bilibili.com" || domain.ends_with(".bilibili.com")
+}
+
+impl fmt::Display for CookieInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cookies = self
+            .cookies
             .iter()
             .map(|entry| entry.to_string())
             .collect::<Vec<String>>()
-            .join("; ")
+            .join("; ");
+        f.write_str(&cookies)
     }
 }
 
@@ -185,7 +674,7 @@ pub struct CookieEntry {
 }
 
 impl CookieEntry {
-    pub(crate) fn to_cookie(&self) -> Cookie {
+    pub(crate) fn to_cookie(&self) -> Cookie<'_> {
         Cookie::build(self.name.clone(), self.value.clone())
             .domain("bilibili.com")
             .finish()
@@ -193,16 +682,16 @@ impl CookieEntry {
 }
 
 impl FromStr for CookieEntry {
-    type Err = anyhow::Error;
+    type Err = Error;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn from_str(s: &str) -> Result<Self> {
         let mut parts = s.splitn(2, '=');
         let name = parts
             .next()
-            .ok_or_else(|| anyhow::anyhow!("CookieEntry::from_str: no name"))?;
+            .ok_or_else(|| Error::Custom("CookieEntry::from_str: no name".into()))?;
         let value = parts
             .next()
-            .ok_or_else(|| anyhow::anyhow!("CookieEntry::from_str: no value"))?;
+            .ok_or_else(|| Error::Custom("CookieEntry::from_str: no value".into()))?;
         Ok(Self {
             name: name.to_string(),
             value: value.to_string(),
@@ -210,9 +699,9 @@ impl FromStr for CookieEntry {
     }
 }
 
-impl ToString for CookieEntry {
-    fn to_string(&self) -> String {
-        format!("{}={}", self.name, self.value)
+impl fmt::Display for CookieEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
     }
 }
 
@@ -228,8 +717,12 @@ pub(crate) struct TokenInfo {
 #[derive(Deserialize, Debug)]
 pub(crate) struct ResponseData {
     pub(crate) code: i32,
+    #[serde(default)]
     pub(crate) data: ResponseValue,
+    #[serde(default)]
     pub(crate) message: String,
+    #[allow(dead_code)]
+    #[serde(default)]
     ttl: i32,
 }
 
@@ -239,3 +732,9 @@ pub(crate) enum ResponseValue {
     Login(Credential),
     Value(serde_json::Value),
 }
+
+impl Default for ResponseValue {
+    fn default() -> Self {
+        ResponseValue::Value(Value::Null)
+    }
+}
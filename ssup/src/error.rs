@@ -0,0 +1,166 @@
+use serde_json::Value;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// ssup 中的错误
+#[derive(Error, Debug)]
+pub enum Error {
+    /// 登录凭据失效，需要刷新或重新登录
+    #[error("credential expired ({code}): {message}")]
+    CredentialExpired { code: i64, message: String },
+
+    /// 请求过于频繁
+    #[error("rate limited ({code}): {message}")]
+    RateLimited { code: i64, message: String },
+
+    /// 标题未通过检查
+    #[error("title rejected ({code}): {message}")]
+    TitleRejected { code: i64, message: String },
+
+    /// 其他接口错误
+    #[error("api error ({code}): {message}")]
+    Api { code: i64, message: String },
+
+    /// 上传接口返回 `OK` 不为 1
+    #[error("server returned not OK: {0}")]
+    NotOk(Value),
+
+    /// 响应不符合预期的格式
+    #[error("unexpected response: {0}")]
+    UnexpectedResponse(String),
+
+    /// 网络错误
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    /// 分块上传后服务端返回的校验值与本地不符
+    #[error("checksum mismatch on part {part_number}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        part_number: usize,
+        expected: String,
+        actual: String,
+    },
+
+    /// 登录所需的 Cookie 缺失
+    #[error("missing cookies: {0}")]
+    MissingCookies(String),
+
+    /// 凭据中没有 Access Token，如网页端扫码登录得到的凭据
+    #[error("credential has no access token")]
+    NoAccessToken,
+
+    /// 登录二维码已过期
+    #[error("qrcode expired")]
+    QrcodeExpired,
+
+    /// 扫码登录超时
+    #[error("login timed out after {0:?}")]
+    LoginTimeout(std::time::Duration),
+
+    /// 没有可用的上传线路
+    #[error("no upload line available")]
+    NoLineAvailable,
+
+    /// 上传被取消，通常是进度接收端已被丢弃
+    #[error("upload cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    UrlEncode(#[from] serde_urlencoded::ser::Error),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl Error {
+    /// 根据 B 站接口返回的 `code` 构造对应的错误
+    pub fn from_code<S>(code: i64, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let message = message.into();
+        match code {
+            // 未登录、Access Key 错误、Token 过期、CSRF 校验失败
+            -101 | -2 | -658 | -111 | 61000 => Error::CredentialExpired { code, message },
+            // 请求过于频繁、请求被拦截、投稿过于频繁
+            -509 | -412 | 21070 | 21540 => Error::RateLimited { code, message },
+            // 标题相关的错误码不固定，以错误信息区分
+            _ if message.contains("标题") => Error::TitleRejected { code, message },
+            _ => Error::Api { code, message },
+        }
+    }
+
+    /// 检查接口返回的 `code`，不为 0 时返回对应的错误
+    pub(crate) fn check(response: &Value) -> Result<()> {
+        match response["code"].as_i64() {
+            Some(0) => Ok(()),
+            Some(code) => Err(Error::from_code(
+                code,
+                response["message"].as_str().unwrap_or_default(),
+            )),
+            None => Err(Error::UnexpectedResponse(response.to_string())),
+        }
+    }
+
+    /// 检查上传接口返回的 `OK`，不为 1 时返回错误
+    pub(crate) fn check_ok(response: Value) -> Result<Value> {
+        if response["OK"] == 1 {
+            Ok(response)
+        } else {
+            Err(Error::NotOk(response))
+        }
+    }
+
+    /// 错误是否可以通过刷新凭据解决
+    pub fn is_credential_expired(&self) -> bool {
+        matches!(self, Error::CredentialExpired { .. })
+    }
+
+    /// 错误是否由请求频率限制导致
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Error::RateLimited { .. })
+    }
+
+    /// 错误是否来自网络或上传服务端，切换线路后有可能成功
+    ///
+    /// 本地读取失败、校验不符或上传被取消时换线路也无济于事。
+    pub fn is_line_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::Network(_) | Error::NotOk(_) | Error::UnexpectedResponse(_)
+        )
+    }
+
+    /// 重试是否有可能成功
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            Error::CredentialExpired { .. } | Error::TitleRejected { .. }
+        )
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for Error {
+    fn from(e: reqwest::header::InvalidHeaderValue) -> Self {
+        Error::Custom(e.to_string())
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderName> for Error {
+    fn from(e: reqwest::header::InvalidHeaderName) -> Self {
+        Error::Custom(e.to_string())
+    }
+}
+
+impl<T> From<tokio::sync::mpsc::error::SendError<T>> for Error {
+    fn from(_: tokio::sync::mpsc::error::SendError<T>) -> Self {
+        Error::Cancelled
+    }
+}
@@ -1,12 +1,20 @@
 mod client;
+mod config;
 pub mod constants;
 mod credential;
+mod error;
+mod limit;
 mod line;
+mod progress;
 mod uploader;
 pub mod video;
 
-pub use client::Client;
-pub use credential::{CookieEntry, CookieInfo, Credential};
+pub use client::{Client, ClientBuilder};
+pub use config::ClientConfig;
+pub use credential::{CookieEntry, CookieInfo, Credential, QrcodeStatus, SmsCode};
+pub use error::{Error, Result};
+pub use limit::{ChunkBudget, RateLimiter};
 pub use line::UploadLine;
-pub use uploader::upos;
-pub use video::VideoId;
+pub use progress::UploadEvent;
+pub use uploader::{bos, cos, gcs, kodo, upos};
+pub use video::{VideoId, VideoUrl};
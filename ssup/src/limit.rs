@@ -0,0 +1,172 @@
+use crate::config::ClientConfig;
+use async_stream::stream;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 限速时每次发送的数据大小
+const SLICE_SIZE: usize = 64 * 1024;
+
+/// 令牌桶限速器
+///
+/// 复制的限速器共享同一个令牌桶，速率可以在上传过程中调整。
+#[derive(Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    /// 字节每秒，为空时不限速
+    rate: Option<u64>,
+    /// 可用的字节数，为负时表示需要等待
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// 创建限速器，`rate` 为空时不限速
+    pub fn new(rate: Option<u64>) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: rate.filter(|&rate| rate > 0),
+                tokens: 0.0,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    /// 当前的速率上限，单位为字节每秒
+    pub fn rate(&self) -> Option<u64> {
+        self.bucket.lock().rate
+    }
+
+    /// 调整速率上限，为空时取消限速
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.bucket.lock();
+        bucket.refill();
+        bucket.rate = rate.filter(|&rate| rate > 0);
+    }
+
+    /// 等待直到可以发送 `bytes` 字节
+    pub async fn acquire(&self, bytes: usize) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock();
+                let rate = match bucket.rate {
+                    Some(rate) => rate as f64,
+                    None => return,
+                };
+                bucket.refill();
+                if bucket.tokens >= 0.0 {
+                    // 允许透支，之后的请求等待补齐
+                    bucket.tokens -= bytes as f64;
+                    return;
+                }
+                Duration::from_secs_f64(-bucket.tokens / rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            // 最多积攒一秒的流量
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.updated = now;
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("rate", &self.rate())
+            .finish()
+    }
+}
+
+/// 所有分P共享的同时上传分块数上限
+///
+/// 复制的上限共享同一组许可，同时上传多个分P时由各分P分配。
+#[derive(Clone, Default)]
+pub struct ChunkBudget {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl ChunkBudget {
+    /// 创建上限，`chunks` 为空时不限制
+    pub fn new(chunks: Option<usize>) -> Self {
+        Self {
+            semaphore: chunks
+                .filter(|&chunks| chunks > 0)
+                .map(|chunks| Arc::new(Semaphore::new(chunks))),
+        }
+    }
+
+    /// 等待直到可以开始读取并上传一个分块，分块上传完成后释放返回的许可
+    pub(crate) async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.semaphore {
+            // 信号量不会被关闭
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Debug for ChunkBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkBudget")
+            .field(
+                "available",
+                &self.semaphore.as_ref().map(|s| s.available_permits()),
+            )
+            .finish()
+    }
+}
+
+/// 上传单个分P时生效的限速
+#[derive(Clone, Debug)]
+pub(crate) struct PartLimit {
+    global: RateLimiter,
+    part: RateLimiter,
+}
+
+impl PartLimit {
+    pub(crate) fn new(config: &ClientConfig) -> Self {
+        Self {
+            global: config.rate_limiter.clone(),
+            part: RateLimiter::new(config.part_rate_limit),
+        }
+    }
+
+    /// 将分块转换为请求体，限速时按限速器分段发送
+    pub(crate) fn body(&self, chunk: Bytes) -> reqwest::Body {
+        if self.global.rate().is_none() && self.part.rate().is_none() {
+            return chunk.into();
+        }
+        let limit = self.clone();
+        reqwest::Body::wrap_stream(stream! {
+            let mut offset = 0;
+            while offset < chunk.len() {
+                let end = chunk.len().min(offset + SLICE_SIZE);
+                limit.global.acquire(end - offset).await;
+                limit.part.acquire(end - offset).await;
+                yield Ok::<_, std::io::Error>(chunk.slice(offset..end));
+                offset = end;
+            }
+        })
+    }
+}
@@ -1,29 +1,64 @@
 use crate::client::Client;
-use crate::uploader::upos::Upos;
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::progress::{Progress, UploadEvent};
+use crate::uploader::bos::Bos;
+use crate::uploader::chunk::{ChunkSource, Md5Reader};
+use crate::uploader::cos::Cos;
+use crate::uploader::gcs::Gcs;
+use crate::uploader::kodo::Kodo;
+use crate::uploader::upos::{self, Upos, UposSession};
 use crate::video::VideoPart;
-use anyhow::bail;
-use futures::TryStreamExt;
+use bytes::Bytes;
+use futures::{future, TryStreamExt};
+use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::Path;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncRead;
 use tokio::sync::mpsc::Sender;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Uploader {
     Upos,
+    Kodo,
+    Bos,
+    Gcs,
+    Cos,
 }
 
 impl Uploader {
+    fn name(&self) -> &'static str {
+        match self {
+            Uploader::Upos => "upos",
+            Uploader::Kodo => "kodo",
+            Uploader::Bos => "bos",
+            Uploader::Gcs => "gcs",
+            Uploader::Cos => "cos",
+        }
+    }
+
     fn profile(&self) -> &'static str {
-        "ugcupos/bup"
+        match self {
+            Uploader::Upos => "ugcupos/bup",
+            // 第三方存储上传完成后需要由 B 站拉取
+            _ => "ugcupos/bupfetch",
+        }
     }
 }
 
+/// 单次测速的默认次数
+const PROBE_SAMPLES: usize = 3;
+/// 单次测速的默认超时时间
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 以 POST 测速时上传的数据大小
+const PROBE_PAYLOAD: usize = 100 * 1024;
+
 /// 上传线路
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct UploadLine {
     os: Uploader,
     probe_url: String,
@@ -37,12 +72,20 @@ impl UploadLine {
         &self.probe_url
     }
 
+    /// 线路名称，如 `bda2`、`kodo`
+    pub fn name(&self) -> &str {
+        self.query
+            .split('&')
+            .find_map(|param| param.strip_prefix("upcdn="))
+            .unwrap_or_else(|| self.os.name())
+    }
+
     pub async fn pre_upload<T, S>(
         &self,
         client: &Client,
         file_name: S,
         total_size: usize,
-    ) -> anyhow::Result<T>
+    ) -> Result<T>
     where
         T: DeserializeOwned,
         S: AsRef<str>,
@@ -60,8 +103,8 @@ impl UploadLine {
         Ok(client
             .client
             .get(format!(
-                "https://member.bilibili.com/preupload?{}",
-                self.query
+                "{}/preupload?{}",
+                client.config.member_url, self.query
             ))
             .query(&query)
             .send()
@@ -70,78 +113,330 @@ impl UploadLine {
             .await?)
     }
 
+    /// 上传本地文件，支持断点续传，不计算整个文件的 MD5
     pub(crate) async fn upload<P>(
         &self,
         client: &Client,
         file_path: P,
         total_size: usize,
-        sx: Sender<usize>,
-    ) -> anyhow::Result<VideoPart>
+        sx: Sender<UploadEvent>,
+    ) -> Result<VideoPart>
     where
         P: AsRef<Path>,
     {
-        match self.os {
-            Uploader::Upos => {
-                log::debug!("Uploading with upos");
-                let file_name = file_path.as_ref().file_name().unwrap().to_str().unwrap();
-                let bucket = self.pre_upload(client, file_name, total_size).await?;
-                let upos = Upos::from(bucket).await?;
+        let file_path = file_path.as_ref();
+        let file_name = file_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Custom(format!("invalid file name: {}", file_path.display())))?;
+        let session_path = client.session_path(file_path);
+        let session = SessionFile {
+            path: &session_path,
+            modified: upos::modified_time(file_path).await?,
+        };
+        let source: ChunkSource = ChunkSource::File(file_path.to_path_buf());
+        self.upload_source(client, source, file_name, total_size, Some(session), sx)
+            .await
+    }
 
-                let mut parts = Vec::new();
-                let stream = upos.upload_stream(file_path.as_ref()).await?;
-                tokio::pin!(stream);
+    /// 从 `reader` 读取 `total_size` 字节并上传，不支持断点续传
+    pub(crate) async fn upload_reader<R>(
+        &self,
+        client: &Client,
+        reader: R,
+        file_name: &str,
+        total_size: usize,
+        sx: Sender<UploadEvent>,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut reader = Md5Reader::new(reader);
+        let source = ChunkSource::Reader(&mut reader);
+        let mut part = self
+            .upload_source(client, source, file_name, total_size, None, sx)
+            .await?;
+        part.md5 = Some(reader.finish());
+        Ok(part)
+    }
 
-                while let Some((part, size)) = stream.try_next().await? {
-                    parts.push(part);
-                    sx.send(size).await?;
+    /// 使用 upos 上传，会话有效时续传，服务端拒绝续传时删除会话并重新上传
+    async fn upload_upos<R>(
+        &self,
+        client: &Client,
+        source: ChunkSource<R>,
+        file_name: &str,
+        total_size: usize,
+        session: Option<SessionFile<'_>>,
+        progress: &Progress,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let session_path = session.map(|session| session.path);
+        let modified = session.map_or(0, |session| session.modified);
+        let saved = match session_path {
+            Some(path) => match UposSession::load(path).await {
+                Some(saved)
+                    if saved.matches(self.name(), file_name, total_size as u64, modified) =>
+                {
+                    Some(saved)
+                }
+                Some(_) => {
+                    log::info!("Discarding stale upload session {}", path.display());
+                    remove_session(session_path).await;
+                    None
+                }
+                None => None,
+            },
+            None => None,
+        };
+
+        let source = match (saved, source) {
+            (Some(saved), ChunkSource::File(file)) => {
+                log::info!("Resuming upload session for {}", file.display());
+                let upos = Upos::resume(&client.config, &saved)?;
+                let resumed: ChunkSource = ChunkSource::File(file.clone());
+                match Self::upload_upos_session(
+                    upos,
+                    saved,
+                    resumed,
+                    file_name,
+                    total_size,
+                    session_path,
+                    progress,
+                )
+                .await
+                {
+                    Err(e) if is_session_rejected(&e) => {
+                        log::warn!("Upload session rejected, starting over: {e}");
+                        remove_session(session_path).await;
+                        progress
+                            .send(UploadEvent::LineStarted {
+                                line: self.name().to_string(),
+                            })
+                            .await?;
+                        ChunkSource::File(file)
+                    }
+                    result => return result,
                 }
-                upos.get_ret_video_info(&parts, file_name).await
             }
+            (_, source) => source,
+        };
+
+        let bucket = self.pre_upload(client, file_name, total_size).await?;
+        let upos = Upos::from(&client.config, bucket).await?;
+        let saved = upos.session(self.name(), file_name, total_size as u64, modified);
+        Self::upload_upos_session(
+            upos,
+            saved,
+            source,
+            file_name,
+            total_size,
+            session_path,
+            progress,
+        )
+        .await
+    }
+
+    /// 上传 `session` 中尚未完成的分块，上传完成后删除会话文件
+    async fn upload_upos_session<R>(
+        upos: Upos,
+        mut session: UposSession,
+        source: ChunkSource<R>,
+        file_name: &str,
+        total_size: usize,
+        session_path: Option<&Path>,
+        progress: &Progress,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let upos = upos.with_progress(progress.clone());
+        save_session(&session, session_path).await;
+
+        // 已完成的分块直接计入进度
+        for (index, bytes) in session.completed_chunks() {
+            progress
+                .send(UploadEvent::ChunkDone {
+                    index,
+                    bytes: bytes as usize,
+                })
+                .await?;
+        }
+
+        let stream = upos.upload_stream(source, total_size as u64, session.completed());
+        tokio::pin!(stream);
+
+        while let Some(part) = stream.try_next().await? {
+            session.push(part);
+            save_session(&session, session_path).await;
         }
+        progress.send(UploadEvent::Merging).await?;
+        let part = upos.get_ret_video_info(session.parts(), file_name).await?;
+        remove_session(session_path).await;
+        Ok(part)
+    }
+
+    /// 仅 upos 线路在提供 `session` 时支持断点续传
+    async fn upload_source<R>(
+        &self,
+        client: &Client,
+        source: ChunkSource<R>,
+        file_name: &str,
+        total_size: usize,
+        session: Option<SessionFile<'_>>,
+        sx: Sender<UploadEvent>,
+    ) -> Result<VideoPart>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let progress = Progress::new(sx);
+        progress
+            .send(UploadEvent::LineStarted {
+                line: self.name().to_string(),
+            })
+            .await?;
+
+        let part = match self.os {
+            Uploader::Upos => {
+                log::debug!("Uploading with upos");
+                self.upload_upos(client, source, file_name, total_size, session, &progress)
+                    .await?
+            }
+            Uploader::Kodo => {
+                log::debug!("Uploading with kodo");
+                let bucket = self.pre_upload(client, file_name, total_size).await?;
+                let kodo = Kodo::from(&client.config, bucket)
+                    .await?
+                    .with_progress(progress.clone());
+                let blocks = kodo
+                    .upload_stream(source, total_size as u64)
+                    .try_collect()
+                    .await?;
+                progress.send(UploadEvent::Merging).await?;
+                kodo.get_ret_video_info(blocks, file_name, total_size)
+                    .await?
+            }
+            Uploader::Bos => {
+                log::debug!("Uploading with bos");
+                let bucket = self.pre_upload(client, file_name, total_size).await?;
+                let bos = Bos::from(&client.config, bucket)
+                    .await?
+                    .with_progress(progress.clone());
+                let parts = bos
+                    .upload_stream(source, total_size as u64)
+                    .try_collect()
+                    .await?;
+                progress.send(UploadEvent::Merging).await?;
+                bos.get_ret_video_info(parts, file_name).await?
+            }
+            Uploader::Gcs => {
+                log::debug!("Uploading with gcs");
+                let bucket = self.pre_upload(client, file_name, total_size).await?;
+                let gcs = Gcs::from(&client.config, bucket)
+                    .await?
+                    .with_progress(progress.clone());
+                gcs.upload_stream(source, total_size)
+                    .try_collect::<()>()
+                    .await?;
+                progress.send(UploadEvent::Merging).await?;
+                gcs.get_ret_video_info(file_name).await?
+            }
+            Uploader::Cos => {
+                log::debug!("Uploading with cos");
+                let bucket = self.pre_upload(client, file_name, total_size).await?;
+                let cos = Cos::from(&client.config, bucket)
+                    .await?
+                    .with_progress(progress.clone());
+                let parts = cos
+                    .upload_stream(source, total_size as u64)
+                    .try_collect()
+                    .await?;
+                progress.send(UploadEvent::Merging).await?;
+                cos.get_ret_video_info(parts, file_name).await?
+            }
+        };
+
+        progress
+            .send(UploadEvent::Finished {
+                filename: part.filename.clone(),
+            })
+            .await?;
+        Ok(part)
     }
 
     /// 挑选条件最好的线路
-    pub async fn auto() -> anyhow::Result<Self> {
+    pub async fn auto(config: &ClientConfig) -> Result<Self> {
+        let mut results = Self::probe(config, PROBE_SAMPLES, PROBE_TIMEOUT).await?;
+        if results.is_empty() {
+            return Err(Error::NoLineAvailable);
+        }
+        Ok(results.swap_remove(0).line)
+    }
+
+    /// 并发测试所有线路，返回按测速结果由好到坏排序的线路
+    ///
+    /// 每条线路测试 `samples` 次，单次测试超过 `timeout` 视为失败，全部失败的线路将被忽略。
+    pub async fn probe(
+        config: &ClientConfig,
+        samples: usize,
+        timeout: Duration,
+    ) -> Result<Vec<ProbeResult>> {
         #[derive(Deserialize)]
         struct ProbeResponse {
-            #[serde(rename = "OK")]
-            ok: u8,
             lines: Vec<UploadLine>,
             probe: serde_json::Value,
         }
-        let res: ProbeResponse = reqwest::get("https://member.bilibili.com/preupload?r=probe")
+        let client = config.http_client()?;
+        let res: serde_json::Value = client
+            .get(format!("{}/preupload?r=probe", config.member_url))
+            .timeout(timeout)
+            .send()
             .await?
             .json()
             .await?;
-        if res.ok != 1 {
-            bail!("TODO in line.rs");
-        }
+        let res: ProbeResponse = serde_json::from_value(Error::check_ok(res)?)?;
 
-        let do_probe = if !res.probe["get"].is_null() {
-            |url| reqwest::Client::new().get(url)
+        // 未指定 GET 时以上传 100K 数据的方式测速
+        let payload = if res.probe["get"].is_null() {
+            Some(Bytes::from(vec![0; PROBE_PAYLOAD]))
         } else {
-            |url| {
-                reqwest::Client::new()
-                    .post(url)
-                    .body(vec![0; (1024. * 0.1 * 1024.) as usize])
-            }
+            None
         };
-        let mut line_chosen: UploadLine = Default::default();
-        for mut line in res.lines {
-            let instant = Instant::now();
-            if do_probe(format!("https:{}", line.probe_url))
-                .send()
-                .await?
-                .status()
-                == 200
-            {
-                line.cost = instant.elapsed().as_millis();
-                if line_chosen.cost > line.cost {
-                    line_chosen = line
+        let probes = res.lines.into_iter().map(|line| {
+            let client = &client;
+            let payload = payload.clone();
+            async move {
+                let url = config.upload_url(&line.probe_url);
+                let mut elapsed = Vec::with_capacity(samples);
+                for _ in 0..samples {
+                    let request = match &payload {
+                        Some(payload) => client.post(&url).body(payload.clone()),
+                        None => client.get(&url),
+                    };
+                    let instant = Instant::now();
+                    match request.timeout(timeout).send().await {
+                        Ok(response) if response.status().is_success() => {
+                            elapsed.push(instant.elapsed())
+                        }
+                        Ok(response) => {
+                            log::debug!("Probe {} failed: {}", line.name(), response.status())
+                        }
+                        Err(e) => log::debug!("Probe {} failed: {e}", line.name()),
+                    }
                 }
-            };
-        }
-        Ok(line_chosen)
+                ProbeResult::new(line, samples, &elapsed, payload.map(|p| p.len()))
+            }
+        });
+
+        let mut results: Vec<_> = future::join_all(probes)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        results.sort_by_key(|result| (result.failures, result.latency));
+        Ok(results)
     }
 
     pub fn bda2() -> Self {
@@ -172,6 +467,72 @@ impl UploadLine {
     }
 }
 
+/// 线路测速结果
+#[derive(Debug)]
+pub struct ProbeResult {
+    pub line: UploadLine,
+    /// 成功测速的平均耗时
+    pub latency: Duration,
+    /// 估算的上传速度，单位为字节每秒，仅在以上传数据方式测速时存在
+    pub throughput: Option<f64>,
+    /// 失败的测速次数
+    pub failures: usize,
+}
+
+impl ProbeResult {
+    fn new(
+        mut line: UploadLine,
+        samples: usize,
+        elapsed: &[Duration],
+        payload: Option<usize>,
+    ) -> Option<Self> {
+        if elapsed.is_empty() {
+            return None;
+        }
+        let latency = elapsed.iter().sum::<Duration>() / elapsed.len() as u32;
+        line.cost = latency.as_millis();
+        Some(Self {
+            line,
+            latency,
+            throughput: payload.map(|size| size as f64 / latency.as_secs_f64()),
+            failures: samples - elapsed.len(),
+        })
+    }
+}
+
+/// 断点续传使用的会话文件
+#[derive(Clone, Copy)]
+struct SessionFile<'a> {
+    path: &'a Path,
+    /// 本地文件的修改时间，Unix 毫秒时间戳
+    modified: u64,
+}
+
+/// 续传时服务端是否拒绝了原有的上传，即 `upload_id` 不存在或已失效
+///
+/// 凭据失效、请求过于频繁等错误不视为拒绝，保留会话以便之后续传。
+fn is_session_rejected(error: &Error) -> bool {
+    match error {
+        Error::Network(e) => matches!(e.status(), Some(StatusCode::NOT_FOUND | StatusCode::GONE)),
+        _ => false,
+    }
+}
+
+async fn remove_session(path: Option<&Path>) {
+    if let Some(path) = path {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
+
+/// 保存上传会话，失败时仅记录警告
+async fn save_session(session: &UposSession, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(e) = session.save(path).await {
+            log::warn!("Failed to save upload session: {e}");
+        }
+    }
+}
+
 impl Default for UploadLine {
     fn default() -> Self {
         let cost = u128::MAX;
@@ -0,0 +1,56 @@
+use crate::error::Result;
+use tokio::sync::mpsc::Sender;
+
+/// 上传单个分P时产生的事件
+#[derive(Debug, Clone)]
+pub enum UploadEvent {
+    /// 开始在线路上上传，切换线路后会重新发送，之前的进度作废
+    LineStarted { line: String },
+    /// 分块开始上传
+    ChunkStarted { index: usize },
+    /// 分块上传完成，续传时已完成的分块也会发送
+    ChunkDone { index: usize, bytes: usize },
+    /// 分块上传失败，即将进行第 `attempt` 次重试
+    ChunkRetry {
+        index: usize,
+        attempt: u32,
+        error: String,
+    },
+    /// 所有分块上传完成，等待服务端合并
+    Merging,
+    /// 分P上传完成，`filename` 为服务端的文件名
+    Finished { filename: String },
+}
+
+/// 上传事件的发送端，未设置接收端时丢弃所有事件
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Progress {
+    sender: Option<Sender<UploadEvent>>,
+}
+
+impl Progress {
+    pub(crate) fn new(sender: Sender<UploadEvent>) -> Self {
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    /// 发送事件，接收端已关闭时返回 [`crate::Error::Cancelled`]
+    pub(crate) async fn send(&self, event: UploadEvent) -> Result<()> {
+        match &self.sender {
+            Some(sender) => Ok(sender.send(event).await?),
+            None => Ok(()),
+        }
+    }
+
+    /// 发送分块重试事件，忽略发送失败
+    pub(crate) async fn retry(&self, index: usize, attempt: u32, error: String) {
+        let _ = self
+            .send(UploadEvent::ChunkRetry {
+                index,
+                attempt,
+                error,
+            })
+            .await;
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::limit::PartLimit;
+use crate::progress::{Progress, UploadEvent};
+use crate::uploader::chunk::{self, read_chunks, ChunkHash, ChunkSource};
+use crate::uploader::utils;
+use crate::video::VideoPart;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_LENGTH, ETAG};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncRead;
+
+/// 百度云分块大小
+const CHUNK_SIZE: usize = 10 * 1024 * 1024;
+
+pub struct Bos {
+    client: reqwest::Client,
+    config: ClientConfig,
+    limit: PartLimit,
+    progress: Progress,
+    bucket: BosBucket,
+    url: String,
+    upload_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BosBucket {
+    bili_filename: String,
+    url: String,
+    post_auth: String,
+    put_auth: String,
+    fetch_url: String,
+    fetch_headers: HashMap<String, String>,
+}
+
+/// 已上传完成的分块
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BosPart {
+    part_number: usize,
+    e_tag: String,
+}
+
+impl Bos {
+    pub async fn from(config: &ClientConfig, mut bucket: BosBucket) -> Result<Self> {
+        let client = utils::client(config, HeaderMap::new())?;
+        let url = config.upload_url(&bucket.url);
+        bucket.fetch_url = config.upload_url(&bucket.fetch_url);
+        let ret: serde_json::Value = utils::send(config, || {
+            client
+                .post(format!("{url}?uploads"))
+                .header(AUTHORIZATION, &bucket.post_auth)
+        })
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+        let upload_id = ret["uploadId"]
+            .as_str()
+            .ok_or_else(|| Error::UnexpectedResponse(ret.to_string()))?
+            .to_string();
+        Ok(Bos {
+            client,
+            config: config.clone(),
+            limit: PartLimit::new(config),
+            progress: Progress::default(),
+            bucket,
+            url,
+            upload_id,
+        })
+    }
+
+    /// 设置上传事件的发送端
+    pub(crate) fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub async fn upload_chunk(&self, chunk: Bytes, part_number: usize) -> Result<BosPart> {
+        let hash = ChunkHash::of(&chunk);
+        let on_retry = |attempt, error| self.progress.retry(part_number - 1, attempt, error);
+        let response = utils::send_with(
+            &self.config,
+            || {
+                self.client
+                    .put(&self.url)
+                    .query(&[
+                        ("partNumber", part_number.to_string()),
+                        ("uploadId", self.upload_id.clone()),
+                    ])
+                    .header(AUTHORIZATION, &self.bucket.put_auth)
+                    .header(CONTENT_LENGTH, chunk.len())
+                    .header("Content-MD5", hash.md5_base64())
+                    .body(self.limit.body(chunk.clone()))
+            },
+            on_retry,
+        )
+        .await?
+        .error_for_status()?;
+        let e_tag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .unwrap_or_default()
+            .trim_matches('"')
+            .to_string();
+        hash.verify_etag(part_number, &e_tag)?;
+        Ok(BosPart { part_number, e_tag })
+    }
+
+    pub(crate) fn upload_stream<'a, R>(
+        &'a self,
+        source: ChunkSource<R>,
+        total_size: u64,
+    ) -> impl Stream<Item = Result<BosPart>> + 'a
+    where
+        R: AsyncRead + Unpin + 'a,
+    {
+        let budget = &self.config.chunk_budget;
+        read_chunks(source, CHUNK_SIZE, total_size, budget, HashSet::new())
+            .map(move |chunk| async move {
+                let chunk = chunk?;
+                self.progress
+                    .send(UploadEvent::ChunkStarted { index: chunk.index })
+                    .await?;
+                let data = chunk.read().await?;
+                let part = self.upload_chunk(data, chunk.index + 1).await?;
+                self.progress
+                    .send(UploadEvent::ChunkDone {
+                        index: chunk.index,
+                        bytes: chunk.len,
+                    })
+                    .await?;
+
+                Ok::<_, Error>(part)
+            })
+            .buffer_unordered(chunk::in_flight(&self.config, CHUNK_SIZE))
+    }
+
+    pub async fn get_ret_video_info<S>(
+        &self,
+        mut parts: Vec<BosPart>,
+        file_name: S,
+    ) -> Result<VideoPart>
+    where
+        S: AsRef<str>,
+    {
+        parts.sort_by_key(|part| part.part_number);
+        utils::send(&self.config, || {
+            self.client
+                .post(&self.url)
+                .query(&[("uploadId", &self.upload_id)])
+                .header(AUTHORIZATION, &self.bucket.post_auth)
+                .json(&json!({ "parts": parts }))
+        })
+        .await?
+        .error_for_status()?;
+
+        utils::fetch(
+            &self.config,
+            &self.bucket.fetch_url,
+            &self.bucket.fetch_headers,
+        )
+        .await?;
+        Ok(VideoPart {
+            title: Some(file_name.as_ref().to_string()),
+            filename: self.bucket.bili_filename.clone(),
+            desc: "".to_string(),
+            line: None,
+            md5: None,
+        })
+    }
+}
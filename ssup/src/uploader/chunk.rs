@@ -0,0 +1,229 @@
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::limit::ChunkBudget;
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{ready, Stream};
+use md5::{Digest, Md5};
+use std::collections::HashSet;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, ReadBuf};
+use tokio::sync::OwnedSemaphorePermit;
+
+/// 分块的数据来源
+pub(crate) enum ChunkSource<R = File> {
+    /// 本地文件，每个分块在上传时按位置读取
+    File(PathBuf),
+    /// 只能顺序读取的数据流，分块在产生时读入内存
+    Reader(R),
+}
+
+/// 待上传的分块，持有 [`ChunkBudget`] 的许可直到被丢弃
+pub(crate) struct Chunk {
+    /// 从 0 开始的分块编号
+    pub index: usize,
+    /// 分块在文件中的起始位置
+    pub offset: u64,
+    pub len: usize,
+    data: ChunkData,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+enum ChunkData {
+    File(Arc<PathBuf>),
+    Loaded(Bytes),
+}
+
+/// 分块的 MD5
+pub(crate) struct ChunkHash {
+    md5: [u8; 16],
+}
+
+impl ChunkHash {
+    pub fn of(data: &[u8]) -> Self {
+        Self {
+            md5: Md5::digest(data).into(),
+        }
+    }
+
+    /// 十六进制的 MD5
+    pub fn md5_hex(&self) -> String {
+        self.md5.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Base64 编码的 MD5，用于 `Content-MD5` 请求头
+    pub fn md5_base64(&self) -> String {
+        base64::encode(self.md5)
+    }
+
+    /// 校验服务端返回的 ETag，非 MD5 格式的 ETag 不做校验
+    pub fn verify_etag(&self, part_number: usize, etag: &str) -> Result<()> {
+        let etag = etag.trim_matches('"');
+        if etag.len() != 32 || !etag.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(());
+        }
+        let expected = self.md5_hex();
+        if etag.eq_ignore_ascii_case(&expected) {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch {
+                part_number,
+                expected,
+                actual: etag.to_string(),
+            })
+        }
+    }
+}
+
+impl Chunk {
+    /// 读取分块数据，本地文件的分块每次调用都会重新读取
+    pub async fn read(&self) -> Result<Bytes> {
+        match &self.data {
+            ChunkData::File(path) => {
+                let mut file = File::open(path.as_ref()).await?;
+                file.seek(SeekFrom::Start(self.offset)).await?;
+                let mut buf = vec![0u8; self.len];
+                file.read_exact(&mut buf)
+                    .await
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::UnexpectedEof => {
+                            Error::Custom(format!("{} was truncated during upload", path.display()))
+                        }
+                        _ => e.into(),
+                    })?;
+                Ok(buf.into())
+            }
+            ChunkData::Loaded(data) => Ok(data.clone()),
+        }
+    }
+}
+
+/// 将 `source` 按 `chunk_size` 分块，跳过编号在 `skip` 中的分块，数据大小与 `total_size`
+/// 不符时返回错误
+///
+/// 每个分块在读取前先从 `budget` 取得许可。本地文件的分块在调用 [`Chunk::read`] 前不会读取，
+/// 被跳过的分块也不会读取；数据流的分块在被拉取时才读取。因此内存中的分块数不超过下游同时处理的
+/// 分块数，也不超过 `budget` 的上限。
+pub(crate) fn read_chunks<R>(
+    source: ChunkSource<R>,
+    chunk_size: usize,
+    total_size: u64,
+    budget: &ChunkBudget,
+    skip: HashSet<usize>,
+) -> impl Stream<Item = Result<Chunk>>
+where
+    R: AsyncRead + Unpin,
+{
+    let budget = budget.clone();
+    try_stream! {
+        let mut offset = 0u64;
+        let mut index = 0;
+        match source {
+            ChunkSource::File(path) => {
+                let size = tokio::fs::metadata(&path).await?.len();
+                if size != total_size {
+                    Err(Error::Custom(format!(
+                        "{} has {size} bytes, expected {total_size}",
+                        path.display()
+                    )))?;
+                }
+                let path = Arc::new(path);
+                while offset < total_size {
+                    let len = chunk_len(chunk_size, offset, total_size);
+                    if !skip.contains(&index) {
+                        let _permit = budget.acquire().await;
+                        yield Chunk { index, offset, len, data: ChunkData::File(path.clone()), _permit };
+                    }
+                    offset += len as u64;
+                    index += 1;
+                }
+            }
+            ChunkSource::Reader(mut reader) => {
+                while offset < total_size {
+                    let len = chunk_len(chunk_size, offset, total_size);
+                    let _permit = budget.acquire().await;
+                    let mut buf = vec![0u8; len];
+                    reader.read_exact(&mut buf).await.map_err(|e| match e.kind() {
+                        ErrorKind::UnexpectedEof => Error::Custom(format!(
+                            "source ended before {total_size} bytes"
+                        )),
+                        _ => e.into(),
+                    })?;
+                    if !skip.contains(&index) {
+                        yield Chunk { index, offset, len, data: ChunkData::Loaded(buf.into()), _permit };
+                    }
+                    offset += len as u64;
+                    index += 1;
+                }
+                if reader.read(&mut [0u8; 1]).await? > 0 {
+                    Err(Error::Custom(format!(
+                        "source is larger than declared size {total_size}"
+                    )))?;
+                }
+            }
+        }
+    }
+}
+
+fn chunk_len(chunk_size: usize, offset: u64, total_size: u64) -> usize {
+    (total_size - offset).min(chunk_size as u64) as usize
+}
+
+/// 同时上传的分块数，受并发数与内存上限限制
+pub(crate) fn in_flight(config: &ClientConfig, chunk_size: usize) -> usize {
+    config
+        .concurrency
+        .min(config.max_in_flight / chunk_size)
+        .max(1)
+}
+
+/// 计算文件的 MD5
+pub(crate) async fn file_md5<P>(path: P) -> Result<String>
+where
+    P: AsRef<Path>,
+{
+    let mut reader = Md5Reader::new(File::open(path).await?);
+    tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+    Ok(reader.finish())
+}
+
+/// 在读取的同时计算 MD5
+pub(crate) struct Md5Reader<R> {
+    inner: R,
+    hasher: Md5,
+}
+
+impl<R> Md5Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Md5::new(),
+        }
+    }
+
+    /// 已读取数据的十六进制 MD5
+    pub fn finish(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<R> AsyncRead for Md5Reader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.hasher.update(&buf.filled()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
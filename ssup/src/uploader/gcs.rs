@@ -0,0 +1,143 @@
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::limit::PartLimit;
+use crate::progress::{Progress, UploadEvent};
+use crate::uploader::chunk::{read_chunks, ChunkSource};
+use crate::uploader::utils;
+use crate::video::VideoPart;
+use bytes::Bytes;
+use futures::Stream;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, LOCATION};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncRead;
+
+/// 分块大小，需为 256K 的整数倍
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+pub struct Gcs {
+    client: reqwest::Client,
+    config: ClientConfig,
+    limit: PartLimit,
+    progress: Progress,
+    bucket: GcsBucket,
+    session_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GcsBucket {
+    bili_filename: String,
+    url: String,
+    fetch_url: String,
+    fetch_headers: HashMap<String, String>,
+}
+
+impl Gcs {
+    pub async fn from(config: &ClientConfig, mut bucket: GcsBucket) -> Result<Self> {
+        let client = utils::client(config, HeaderMap::new())?;
+        bucket.fetch_url = config.upload_url(&bucket.fetch_url);
+        let response = utils::send(config, || {
+            client
+                .post(config.upload_url(&bucket.url))
+                .header("x-goog-resumable", "start")
+        })
+        .await?
+        .error_for_status()?;
+        let session_url = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(|| Error::UnexpectedResponse("no resumable session returned".into()))?
+            .to_string();
+        Ok(Gcs {
+            client,
+            config: config.clone(),
+            limit: PartLimit::new(config),
+            progress: Progress::default(),
+            bucket,
+            session_url,
+        })
+    }
+
+    /// 设置上传事件的发送端
+    pub(crate) fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub async fn upload_chunk(&self, chunk: Bytes, start: usize, total_size: usize) -> Result<()> {
+        let end = start + chunk.len();
+        let index = start / CHUNK_SIZE;
+        let on_retry = |attempt, error| self.progress.retry(index, attempt, error);
+        let response = utils::send_with(
+            &self.config,
+            || {
+                self.client
+                    .put(&self.session_url)
+                    .header(
+                        CONTENT_RANGE,
+                        format!("bytes {start}-{}/{total_size}", end.saturating_sub(1)),
+                    )
+                    .header(CONTENT_LENGTH, chunk.len())
+                    .body(self.limit.body(chunk.clone()))
+            },
+            on_retry,
+        )
+        .await?;
+        match response.status() {
+            // 308 表示分块已接收，等待后续分块
+            StatusCode::PERMANENT_REDIRECT | StatusCode::OK | StatusCode::CREATED => Ok(()),
+            status => Err(Error::UnexpectedResponse(format!(
+                "gcs chunk upload failed: {status}"
+            ))),
+        }
+    }
+
+    /// 可续传上传要求按顺序上传分块
+    pub(crate) fn upload_stream<'a, R>(
+        &'a self,
+        source: ChunkSource<R>,
+        total_size: usize,
+    ) -> impl Stream<Item = Result<()>> + 'a
+    where
+        R: AsyncRead + Unpin + 'a,
+    {
+        async_stream::try_stream! {
+            let mut start = 0;
+            let budget = &self.config.chunk_budget;
+            for await chunk in read_chunks(source, CHUNK_SIZE, total_size as u64, budget, HashSet::new()) {
+                let chunk = chunk?;
+                let (index, len) = (chunk.index, chunk.len);
+                self.progress.send(UploadEvent::ChunkStarted { index }).await?;
+                self.upload_chunk(chunk.read().await?, start, total_size).await?;
+                // 释放分块占用的许可
+                drop(chunk);
+                start += len;
+                self.progress
+                    .send(UploadEvent::ChunkDone { index, bytes: len })
+                    .await?;
+                yield;
+            }
+        }
+    }
+
+    pub async fn get_ret_video_info<S>(&self, file_name: S) -> Result<VideoPart>
+    where
+        S: AsRef<str>,
+    {
+        utils::fetch(
+            &self.config,
+            &self.bucket.fetch_url,
+            &self.bucket.fetch_headers,
+        )
+        .await?;
+        Ok(VideoPart {
+            title: Some(file_name.as_ref().to_string()),
+            filename: self.bucket.bili_filename.clone(),
+            desc: "".to_string(),
+            line: None,
+            md5: None,
+        })
+    }
+}
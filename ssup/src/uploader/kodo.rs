@@ -0,0 +1,161 @@
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::limit::PartLimit;
+use crate::progress::{Progress, UploadEvent};
+use crate::uploader::chunk::{self, read_chunks, ChunkSource};
+use crate::uploader::utils;
+use crate::video::VideoPart;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncRead;
+
+/// 七牛云的块大小固定为 4M
+const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+pub struct Kodo {
+    client: reqwest::Client,
+    config: ClientConfig,
+    limit: PartLimit,
+    progress: Progress,
+    bucket: KodoBucket,
+    url: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct KodoBucket {
+    bili_filename: String,
+    endpoint: String,
+    uptoken: String,
+    key: String,
+    fetch_url: String,
+    fetch_headers: HashMap<String, String>,
+}
+
+/// 已上传完成的块
+#[derive(Debug)]
+pub struct KodoBlock {
+    index: usize,
+    ctx: String,
+}
+
+impl Kodo {
+    pub async fn from(config: &ClientConfig, mut bucket: KodoBucket) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("UpToken {}", bucket.uptoken))?,
+        );
+        let client = utils::client(config, headers)?;
+        let url = config.upload_url(&bucket.endpoint);
+        bucket.fetch_url = config.upload_url(&bucket.fetch_url);
+        Ok(Kodo {
+            client,
+            config: config.clone(),
+            limit: PartLimit::new(config),
+            progress: Progress::default(),
+            bucket,
+            url,
+        })
+    }
+
+    /// 设置上传事件的发送端
+    pub(crate) fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub async fn upload_block(&self, block: Bytes, index: usize) -> Result<KodoBlock> {
+        let len = block.len();
+        let on_retry = |attempt, error| self.progress.retry(index, attempt, error);
+        let ret: serde_json::Value = utils::send_with(
+            &self.config,
+            || {
+                self.client
+                    .post(format!("{}/mkblk/{len}", self.url))
+                    .header(CONTENT_LENGTH, block.len())
+                    .body(self.limit.body(block.clone()))
+            },
+            on_retry,
+        )
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+        let ctx = ret["ctx"]
+            .as_str()
+            .ok_or_else(|| Error::UnexpectedResponse(ret.to_string()))?
+            .to_string();
+        Ok(KodoBlock { index, ctx })
+    }
+
+    pub(crate) fn upload_stream<'a, R>(
+        &'a self,
+        source: ChunkSource<R>,
+        total_size: u64,
+    ) -> impl Stream<Item = Result<KodoBlock>> + 'a
+    where
+        R: AsyncRead + Unpin + 'a,
+    {
+        let budget = &self.config.chunk_budget;
+        read_chunks(source, BLOCK_SIZE, total_size, budget, HashSet::new())
+            .map(move |chunk| async move {
+                let chunk = chunk?;
+                self.progress
+                    .send(UploadEvent::ChunkStarted { index: chunk.index })
+                    .await?;
+                let data = chunk.read().await?;
+                let block = self.upload_block(data, chunk.index).await?;
+                self.progress
+                    .send(UploadEvent::ChunkDone {
+                        index: chunk.index,
+                        bytes: chunk.len,
+                    })
+                    .await?;
+
+                Ok::<_, Error>(block)
+            })
+            .buffer_unordered(chunk::in_flight(&self.config, BLOCK_SIZE))
+    }
+
+    pub async fn get_ret_video_info<S>(
+        &self,
+        mut blocks: Vec<KodoBlock>,
+        file_name: S,
+        total_size: usize,
+    ) -> Result<VideoPart>
+    where
+        S: AsRef<str>,
+    {
+        blocks.sort_by_key(|block| block.index);
+        let ctx = blocks
+            .iter()
+            .map(|block| block.ctx.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let key = base64::encode_config(&self.bucket.key, base64::URL_SAFE);
+        utils::send(&self.config, || {
+            self.client
+                .post(format!("{}/mkfile/{total_size}/key/{key}", self.url))
+                .body(ctx.clone())
+        })
+        .await?
+        .error_for_status()?;
+
+        utils::fetch(
+            &self.config,
+            &self.bucket.fetch_url,
+            &self.bucket.fetch_headers,
+        )
+        .await?;
+        Ok(VideoPart {
+            title: Some(file_name.as_ref().to_string()),
+            filename: self.bucket.bili_filename.clone(),
+            desc: "".to_string(),
+            line: None,
+            md5: None,
+        })
+    }
+}
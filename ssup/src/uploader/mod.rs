@@ -1,2 +1,7 @@
+pub mod bos;
+pub(crate) mod chunk;
+pub mod cos;
+pub mod gcs;
+pub mod kodo;
 pub mod upos;
 pub(crate) mod utils;
@@ -1,26 +1,34 @@
-use crate::constants::{CONCURRENCY, USER_AGENT};
-use crate::uploader::utils::read_chunk;
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use crate::limit::PartLimit;
+use crate::progress::{Progress, UploadEvent};
+use crate::uploader::chunk::{self, read_chunks, ChunkHash, ChunkSource};
+use crate::uploader::utils;
 use crate::video::VideoPart;
-use anyhow::{anyhow, bail};
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::policies::ExponentialBackoff;
-use reqwest_retry::RetryTransientMiddleware;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, ETAG};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::json;
+use std::collections::HashSet;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncRead;
+
+/// 上传会话的有效期，超过后服务端的 `upload_id` 可能已失效
+pub const SESSION_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
 
 pub struct Upos {
-    client: ClientWithMiddleware,
+    client: reqwest::Client,
+    config: ClientConfig,
+    limit: PartLimit,
+    progress: Progress,
     bucket: UposBucket,
     url: String,
     upload_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UposBucket {
     chunk_size: usize,
     auth: String,
@@ -29,6 +37,107 @@ pub struct UposBucket {
     upos_uri: String,
 }
 
+/// 已上传完成的分块
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UposPart {
+    part_number: usize,
+    e_tag: String,
+}
+
+/// 用于断点续传的上传会话
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UposSession {
+    /// 上传使用的线路
+    line: String,
+    /// 上传的文件名
+    file_name: String,
+    /// 文件总大小
+    total_size: u64,
+    /// 文件的修改时间，Unix 毫秒时间戳
+    #[serde(default)]
+    modified: u64,
+    /// 会话的创建时间，Unix 秒时间戳
+    #[serde(default)]
+    created: u64,
+    upload_id: String,
+    bucket: UposBucket,
+    /// 已上传完成的分块
+    parts: Vec<UposPart>,
+}
+
+impl UposSession {
+    /// 读取会话文件，文件不存在或无法解析时返回 `None`
+    pub async fn load<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let session = tokio::fs::read(path).await.ok()?;
+        serde_json::from_slice(&session).ok()
+    }
+
+    /// 写入会话文件
+    pub async fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        tokio::fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    /// 会话是否属于该线路上的该文件，且未超过有效期
+    ///
+    /// 文件在上次上传后被修改时，即使大小相同也不再续传。
+    pub fn matches(&self, line: &str, file_name: &str, total_size: u64, modified: u64) -> bool {
+        self.line == line
+            && self.file_name == file_name
+            && self.total_size == total_size
+            && self.modified == modified
+            && unix_secs().saturating_sub(self.created) < SESSION_LIFETIME.as_secs()
+    }
+
+    /// 上传使用的线路
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// 分块大小
+    pub fn chunk_size(&self) -> usize {
+        self.bucket.chunk_size
+    }
+
+    /// 已上传完成的分块编号
+    pub fn completed(&self) -> HashSet<usize> {
+        self.parts.iter().map(|part| part.part_number).collect()
+    }
+
+    /// 已上传完成的分块，依次为从 0 开始的分块编号和分块大小
+    pub fn completed_chunks(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        let chunk_size = self.chunk_size() as u64;
+        self.parts.iter().map(move |part| {
+            let start = (part.part_number as u64 - 1) * chunk_size;
+            let size = chunk_size.min(self.total_size.saturating_sub(start));
+            (part.part_number - 1, size)
+        })
+    }
+
+    /// 已上传完成的字节数
+    pub fn uploaded_size(&self) -> u64 {
+        self.completed_chunks().map(|(_, size)| size).sum()
+    }
+
+    /// 记录已完成的分块
+    pub fn push(&mut self, part: UposPart) {
+        self.parts.push(part);
+    }
+
+    /// 按分块编号排序的已完成分块
+    pub fn parts(&mut self) -> &[UposPart] {
+        self.parts.sort_by_key(|part| part.part_number);
+        &self.parts
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Protocol<'a> {
@@ -43,39 +152,86 @@ pub struct Protocol<'a> {
 }
 
 impl Upos {
-    pub async fn from(bucket: UposBucket) -> anyhow::Result<Self> {
-        let mut headers = HeaderMap::new();
-        headers.insert("X-Upos-Auth", HeaderValue::from_str(&bucket.auth)?);
-        let client = reqwest::Client::builder()
-            .user_agent(USER_AGENT.read().as_str())
-            .default_headers(headers)
-            .timeout(Duration::new(300, 0))
-            .build()
-            .unwrap();
-        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-        let client = ClientBuilder::new(client)
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-            .build();
-        let url = format!(
-            "https:{}/{}",
-            bucket.endpoint,
-            bucket.upos_uri.replace("upos://", "")
-        );
-        let ret: serde_json::Value = client
-            .post(format!("{url}?uploads&output=json"))
-            .send()
-            .await?
-            .json()
-            .await?;
-        let upload_id = ret["upload_id"].as_str().unwrap().into();
+    /// 创建新的上传
+    pub async fn from(config: &ClientConfig, bucket: UposBucket) -> Result<Self> {
+        let (client, url) = Self::client(config, &bucket)?;
+        let ret: serde_json::Value =
+            utils::send(config, || client.post(format!("{url}?uploads&output=json")))
+                .await?
+                .json()
+                .await?;
+        let upload_id = ret["upload_id"]
+            .as_str()
+            .ok_or_else(|| Error::UnexpectedResponse(ret.to_string()))?
+            .into();
         Ok(Upos {
             client,
+            config: config.clone(),
+            limit: PartLimit::new(config),
+            progress: Progress::default(),
             bucket,
             url,
             upload_id,
         })
     }
 
+    /// 从会话中恢复上传
+    pub fn resume(config: &ClientConfig, session: &UposSession) -> Result<Self> {
+        let bucket = session.bucket.clone();
+        let (client, url) = Self::client(config, &bucket)?;
+        Ok(Upos {
+            client,
+            config: config.clone(),
+            limit: PartLimit::new(config),
+            progress: Progress::default(),
+            bucket,
+            url,
+            upload_id: session.upload_id.clone(),
+        })
+    }
+
+    /// 为当前上传创建新的会话，`modified` 为文件的修改时间
+    pub fn session<L, S>(
+        &self,
+        line: L,
+        file_name: S,
+        total_size: u64,
+        modified: u64,
+    ) -> UposSession
+    where
+        L: Into<String>,
+        S: Into<String>,
+    {
+        UposSession {
+            line: line.into(),
+            file_name: file_name.into(),
+            total_size,
+            modified,
+            created: unix_secs(),
+            upload_id: self.upload_id.clone(),
+            bucket: self.bucket.clone(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// 设置上传事件的发送端
+    pub(crate) fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    fn client(config: &ClientConfig, bucket: &UposBucket) -> Result<(reqwest::Client, String)> {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Upos-Auth", HeaderValue::from_str(&bucket.auth)?);
+        let client = utils::client(config, headers)?;
+        let url = format!(
+            "{}/{}",
+            config.upload_url(&bucket.endpoint),
+            bucket.upos_uri.replace("upos://", "")
+        );
+        Ok((client, url))
+    }
+
     pub async fn upload_chunk(
         &self,
         chunk: Bytes,
@@ -83,7 +239,7 @@ impl Upos {
         chunks_num: usize,
         start: usize,
         total_size: u64,
-    ) -> anyhow::Result<Value> {
+    ) -> Result<UposPart> {
         let len = chunk.len();
         let params = Protocol {
             upload_id: &self.upload_id,
@@ -96,52 +252,89 @@ impl Upos {
             end: start + len,
         };
 
-        let url = self.url.clone();
-        let response = self
-            .client
-            .put(url)
-            .query(&params)
-            .body(chunk)
-            .send()
-            .await?;
-        response.error_for_status()?;
+        let hash = ChunkHash::of(&chunk);
+        let on_retry = |attempt, error| self.progress.retry(current_chunk, attempt, error);
+        let response = utils::send_with(
+            &self.config,
+            || {
+                self.client
+                    .put(&self.url)
+                    .query(&params)
+                    .header(CONTENT_LENGTH, chunk.len())
+                    .body(self.limit.body(chunk.clone()))
+            },
+            on_retry,
+        )
+        .await?
+        .error_for_status()?;
 
-        Ok(json!({"partNumber": params.part_number, "eTag": "etag"}))
+        // 未返回 ETag 时以分块的 MD5 代替
+        let e_tag = match response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+        {
+            Some(etag) => {
+                hash.verify_etag(params.part_number, etag)?;
+                etag.trim_matches('"').to_string()
+            }
+            None => hash.md5_hex(),
+        };
+        Ok(UposPart {
+            part_number: params.part_number,
+            e_tag,
+        })
     }
 
-    pub(crate) async fn upload_stream<P>(
-        &self,
-        file_path: P,
-    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<(Value, usize)>> + '_>
+    /// 上传文件，跳过 `completed` 中已完成的分块，本地文件中已完成的分块不会被读取
+    pub(crate) fn upload_stream<'a, R>(
+        &'a self,
+        source: ChunkSource<R>,
+        total_size: u64,
+        completed: HashSet<usize>,
+    ) -> impl Stream<Item = Result<UposPart>> + 'a
     where
-        P: AsRef<Path>,
+        R: AsyncRead + Unpin + 'a,
     {
-        let file = tokio::fs::File::open(file_path.as_ref()).await?;
-
-        let total_size = file.metadata().await?.len();
         let chunk_size = self.bucket.chunk_size;
         let chunks_num = (total_size as f64 / chunk_size as f64).ceil() as usize; // 获取分块数量
 
-        let stream = read_chunk(file, chunk_size)
-            .enumerate()
-            .map(move |(i, chunk)| async move {
+        // 已完成的分块以从 1 开始的分块号记录
+        let skip = completed
+            .iter()
+            .map(|part_number| part_number - 1)
+            .collect();
+        let budget = &self.config.chunk_budget;
+        read_chunks(source, chunk_size, total_size, budget, skip)
+            .map(move |chunk| async move {
                 let chunk = chunk?;
-                let len = chunk.len();
+                self.progress
+                    .send(UploadEvent::ChunkStarted { index: chunk.index })
+                    .await?;
+                let data = chunk.read().await?;
                 let part = self
-                    .upload_chunk(chunk, i, chunks_num, i * chunk_size, total_size)
+                    .upload_chunk(
+                        data,
+                        chunk.index,
+                        chunks_num,
+                        chunk.offset as usize,
+                        total_size,
+                    )
                     .await?;
 
-                Ok::<_, anyhow::Error>((part, len))
+                self.progress
+                    .send(UploadEvent::ChunkDone {
+                        index: chunk.index,
+                        bytes: chunk.len,
+                    })
+                    .await?;
+
+                Ok::<_, Error>(part)
             })
-            .buffer_unordered(*CONCURRENCY.read());
-        Ok(stream)
+            .buffer_unordered(chunk::in_flight(&self.config, chunk_size))
     }
 
-    pub async fn get_ret_video_info<S>(
-        &self,
-        parts: &[Value],
-        file_name: S,
-    ) -> anyhow::Result<VideoPart>
+    pub async fn get_ret_video_info<S>(&self, parts: &[UposPart], file_name: S) -> Result<VideoPart>
     where
         S: AsRef<str>,
     {
@@ -152,26 +345,43 @@ impl Upos {
             "output": "json",
             "profile": "ugcupos/bup"
         });
-        let res: serde_json::Value = self
-            .client
-            .post(&self.url)
-            .query(&value)
-            .json(&json!({ "parts": parts }))
-            .send()
-            .await?
-            .json()
-            .await?;
-        if res["OK"] != 1 {
-            bail!("{}", res)
-        }
+        let res: serde_json::Value = utils::send(&self.config, || {
+            self.client
+                .post(&self.url)
+                .query(&value)
+                .json(&json!({ "parts": parts }))
+        })
+        .await?
+        .json()
+        .await?;
+        Error::check_ok(res)?;
         Ok(VideoPart {
             title: Some(file_name.as_ref().to_string()),
             filename: Path::new(&self.bucket.upos_uri)
                 .file_stem()
-                .ok_or(anyhow!("no file stem found"))?
+                .ok_or_else(|| Error::UnexpectedResponse(self.bucket.upos_uri.clone()))?
                 .to_string_lossy()
                 .to_string(),
             desc: "".to_string(),
+            line: None,
+            md5: None,
         })
     }
 }
+
+/// 文件的修改时间，Unix 毫秒时间戳
+pub(crate) async fn modified_time<P>(path: P) -> Result<u64>
+where
+    P: AsRef<Path>,
+{
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64))
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
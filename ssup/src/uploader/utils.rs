@@ -1,31 +1,96 @@
-use async_stream::try_stream;
-use bytes::{BufMut, Bytes, BytesMut};
-use futures::Stream;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use crate::config::ClientConfig;
+use crate::error::{Error, Result};
+use futures::{future, Future};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::time::Duration;
 
-pub(crate) fn read_chunk(
-    mut file: File,
-    chunk_size: usize,
-) -> impl Stream<Item = anyhow::Result<Bytes>> {
-    let mut buffer = vec![0u8; chunk_size];
+/// 创建上传分块使用的客户端
+pub(crate) fn client(config: &ClientConfig, headers: HeaderMap) -> Result<reqwest::Client> {
+    Ok(config
+        .http_builder()?
+        .default_headers(headers)
+        .timeout(config.upload_timeout)
+        .build()?)
+}
+
+/// 发送请求，遇到网络错误或服务端暂时不可用时按配置重试
+///
+/// 每次尝试都会调用 `request` 重新构造请求，以便重新发送流式的请求体。
+pub(crate) async fn send<F>(config: &ClientConfig, request: F) -> Result<Response>
+where
+    F: FnMut() -> RequestBuilder,
+{
+    send_with(config, request, |_, _| future::ready(())).await
+}
 
-    let mut buf = BytesMut::with_capacity(chunk_size);
-    try_stream! {
-        loop {
-            let n = file.read(&mut buffer).await?;
-            let remaining = chunk_size - buf.len();
-            if remaining >= n {
-                buf.put_slice(&buffer[..n]);
-            } else {
-                buf.put_slice(&buffer[..remaining]);
-                yield buf.split().freeze();
-                buf.put_slice(&buffer[remaining..n]);
-            }
-            if n == 0 {
-                yield buf.split().freeze();
-                return;
-            }
+/// 与 [`send`] 相同，每次重试前以重试次数和失败原因调用 `on_retry`
+pub(crate) async fn send_with<F, R, Fut>(
+    config: &ClientConfig,
+    mut request: F,
+    mut on_retry: R,
+) -> Result<Response>
+where
+    F: FnMut() -> RequestBuilder,
+    R: FnMut(u32, String) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = request().send().await;
+        let retry = match &result {
+            Ok(response) => is_transient_status(response.status()),
+            Err(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+        };
+        if !retry || attempt >= config.max_retries {
+            return Ok(result?);
         }
+
+        let delay = backoff(config, attempt);
+        let reason = match &result {
+            Ok(response) => response.status().to_string(),
+            Err(e) => e.to_string(),
+        };
+        log::debug!("Retrying in {delay:?}: {reason}");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+        on_retry(attempt, reason).await;
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// 第 `attempt` 次重试前等待的时间
+fn backoff(config: &ClientConfig, attempt: u32) -> Duration {
+    config
+        .min_retry_interval
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(config.max_retry_interval)
+}
+
+/// 通知 B 站从第三方存储中拉取已上传完成的文件
+pub(crate) async fn fetch(
+    config: &ClientConfig,
+    fetch_url: &str,
+    fetch_headers: &HashMap<String, String>,
+) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in fetch_headers {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
     }
+    let client = client(config, HeaderMap::new())?;
+    let res: serde_json::Value = send(config, || client.post(fetch_url).headers(headers.clone()))
+        .await?
+        .json()
+        .await?;
+    Error::check_ok(res)?;
+    Ok(())
 }
@@ -1,3 +1,6 @@
+use crate::config::ClientConfig;
+use crate::error::Error;
+use reqwest::header::LOCATION;
 use serde::{Deserialize, Serialize};
 use std::num::ParseIntError;
 use std::str::FromStr;
@@ -42,6 +45,20 @@ pub struct VideoPart {
     pub title: Option<String>,
     pub filename: String,
     pub desc: String,
+
+    /// 完成上传的线路，不参与投稿
+    #[serde(skip)]
+    pub line: Option<String>,
+    /// 上传文件的 MD5，不参与投稿
+    #[serde(skip)]
+    pub md5: Option<String>,
+}
+
+/// 投稿结果
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubmitResult {
+    pub aid: u64,
+    pub bvid: String,
 }
 
 /// 视频 ID
@@ -51,20 +68,87 @@ pub enum VideoId {
     BVId(String),
 }
 
+/// AV 号与 BV 号互相转换使用的参数
+const BV_ALPHABET: &[u8] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+const BV_XOR: u64 = 23442827791579;
+const BV_MASK: u64 = (1 << 51) - 1;
+const BV_LEN: usize = 12;
+
+impl VideoId {
+    /// 校验并构造 BV 号，忽略 `BV` 前缀的大小写
+    pub fn from_bvid(bvid: &str) -> Result<Self, String> {
+        if !is_valid_bvid(bvid) {
+            return Err(format!("invalid bvid: {bvid}"));
+        }
+        Ok(VideoId::BVId(format!("BV{}", &bvid[2..])))
+    }
+
+    /// 视频的 AV 号，BV 号在本地转换，BV 号无效时返回 `None`
+    pub fn aid(&self) -> Option<u64> {
+        match self {
+            VideoId::AId(aid) => Some(*aid),
+            VideoId::BVId(bvid) if !is_valid_bvid(bvid) => None,
+            VideoId::BVId(bvid) => {
+                let mut bytes = bvid.as_bytes().to_vec();
+                bytes.swap(3, 9);
+                bytes.swap(4, 7);
+                let value = bytes[3..].iter().fold(0, |value, c| {
+                    let index = BV_ALPHABET.iter().position(|a| a == c).unwrap_or_default();
+                    value * BV_ALPHABET.len() as u64 + index as u64
+                });
+                Some((value & BV_MASK) ^ BV_XOR)
+            }
+        }
+    }
+
+    /// 视频的 BV 号，AV 号在本地转换
+    pub fn bvid(&self) -> String {
+        match self {
+            VideoId::AId(aid) => {
+                let mut bytes = *b"BV1000000000";
+                let mut value = ((BV_MASK + 1) | aid) ^ BV_XOR;
+                let base = BV_ALPHABET.len() as u64;
+                for byte in bytes[3..].iter_mut().rev() {
+                    *byte = BV_ALPHABET[(value % base) as usize];
+                    value /= base;
+                }
+                bytes.swap(3, 9);
+                bytes.swap(4, 7);
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            VideoId::BVId(bvid) => bvid.clone(),
+        }
+    }
+}
+
+fn is_valid_bvid(bvid: &str) -> bool {
+    let bytes = bvid.as_bytes();
+    bytes.len() == BV_LEN
+        && bytes[..2].eq_ignore_ascii_case(b"BV")
+        && bytes[2] == b'1'
+        && bytes[3..].iter().all(|c| BV_ALPHABET.contains(c))
+}
+
 impl FromStr for VideoId {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
 
-        if s.starts_with("av") {
+        if s.contains('/') {
+            // https://www.bilibili.com/video/BV1kS4y1P7vA?p=2
+            Ok(VideoUrl::from_str(s)?.id)
+        } else if let Some(aid) = s.strip_prefix("av").or_else(|| s.strip_prefix("AV")) {
             // av{number}
             Ok(VideoId::AId(
-                s[2..].parse().map_err(|e: ParseIntError| e.to_string())?,
+                aid.parse().map_err(|e: ParseIntError| e.to_string())?,
             ))
-        } else if s.starts_with("BV") {
+        } else if s
+            .get(..2)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("BV"))
+        {
             // BV1kS4y1P7vA
-            Ok(VideoId::BVId(s.to_string()))
+            VideoId::from_bvid(s)
         } else {
             // {number}
             Ok(VideoId::AId(
@@ -74,7 +158,72 @@ impl FromStr for VideoId {
     }
 }
 
-#[derive(Serialize, Debug)]
+/// 视频链接，可带有分P编号
+#[derive(Clone, Debug)]
+pub struct VideoUrl {
+    pub id: VideoId,
+    /// 从 1 开始的分P编号，对应链接中的 `p` 参数
+    pub part: Option<usize>,
+}
+
+impl VideoUrl {
+    /// 解析视频 ID 或链接，无法直接解析的链接视为短链接，
+    /// 如 `https://b23.tv/xxxxxx`，请求后按跳转的地址解析
+    pub async fn resolve(config: &ClientConfig, s: &str) -> crate::Result<Self> {
+        let s = s.trim();
+        match Self::from_str(s) {
+            Ok(url) => Ok(url),
+            Err(e) if !s.contains('/') => Err(Error::Custom(e)),
+            Err(_) => {
+                let url = if s.contains("://") {
+                    s.to_string()
+                } else {
+                    format!("https://{s}")
+                };
+                let response = config
+                    .http_builder()?
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()?
+                    .get(url)
+                    .send()
+                    .await?;
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or_else(|| Error::UnexpectedResponse(format!("no redirect for {s}")))?;
+                Self::from_str(location).map_err(Error::Custom)
+            }
+        }
+    }
+}
+
+impl FromStr for VideoUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (path, query) = s.split_once('?').unwrap_or((s, ""));
+        let query = query.split('#').next().unwrap_or_default();
+        let path = path.split('#').next().unwrap_or_default();
+        let id = match path.split_once("/video/") {
+            Some((_, id)) => id.split('/').next().unwrap_or_default(),
+            None if !path.contains('/') => path,
+            None => return Err(format!("not a video url: {s}")),
+        };
+        let part = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("p="))
+            .map(|part| part.parse().map_err(|e: ParseIntError| e.to_string()))
+            .transpose()?;
+        Ok(VideoUrl {
+            id: VideoId::from_str(id)?,
+            part,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct EditVideo {
     /// 视频 ID
     pub aid: u64,
@@ -96,6 +245,7 @@ pub struct EditVideo {
     /// 由 `,` 连接的 Tag
     pub tag: String,
     /// 分P
+    #[serde(default)]
     pub videos: Vec<EditVideoPart>,
     /// 秒为单位的定时投稿时间
     #[serde(rename = "dtime")]
@@ -136,3 +286,77 @@ pub struct VideoCardItem {
     /// 章节备注说明
     pub content: String,
 }
+
+/// 稿件状态，用于筛选稿件列表
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveStatus {
+    /// 已通过
+    Published,
+    /// 审核中
+    Reviewing,
+    /// 未通过
+    Rejected,
+}
+
+impl ArchiveStatus {
+    /// 稿件列表接口中 `status` 参数的取值
+    pub(crate) fn as_query(&self) -> &'static str {
+        match self {
+            ArchiveStatus::Published => "pubed",
+            ArchiveStatus::Reviewing => "is_pubing",
+            ArchiveStatus::Rejected => "not_pubed",
+        }
+    }
+}
+
+/// 稿件列表中的稿件
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveItem {
+    pub aid: u64,
+    pub bvid: String,
+    pub title: String,
+    /// 稿件状态码，不小于 0 时为已通过
+    pub state: i64,
+    /// 稿件状态的说明
+    #[serde(default)]
+    pub state_desc: String,
+    /// 未通过的原因
+    #[serde(default)]
+    pub reject_reason: String,
+    /// 秒为单位的投稿时间
+    #[serde(default)]
+    pub ctime: i64,
+    /// 秒为单位的发布时间
+    #[serde(default)]
+    pub ptime: i64,
+}
+
+impl ArchiveItem {
+    /// 按状态码判断稿件状态
+    pub fn status(&self) -> ArchiveStatus {
+        match self.state {
+            state if state >= 0 => ArchiveStatus::Published,
+            // 打回、锁定、转码失败
+            -2 | -4 | -16 => ArchiveStatus::Rejected,
+            _ => ArchiveStatus::Reviewing,
+        }
+    }
+}
+
+/// 一页稿件列表
+#[derive(Serialize, Debug, Clone)]
+pub struct ArchivePage {
+    pub archives: Vec<ArchiveItem>,
+    /// 从 1 开始的页码
+    pub page: u32,
+    pub page_size: u32,
+    /// 符合筛选条件的稿件总数
+    pub total: u64,
+}
+
+impl ArchivePage {
+    /// 是否还有下一页
+    pub fn has_next(&self) -> bool {
+        (self.page as u64) * (self.page_size as u64) < self.total
+    }
+}
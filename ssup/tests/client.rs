@@ -0,0 +1,1002 @@
+mod common;
+
+use common::{MockServer, Recorded, CHUNK_SIZE, FETCH_PATH, UPOS_PATH};
+use hyper::Method;
+use ssup::video::{ArchiveStatus, Subtitle, Video, VideoPart};
+use ssup::{
+    Client, ClientConfig, CookieInfo, Credential, QrcodeStatus, UploadEvent, UploadLine, VideoId,
+    VideoUrl,
+};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+
+fn client(server: &MockServer, session_dir: &Path) -> Client {
+    Client::builder()
+        .config(server.config())
+        .line(UploadLine::bda2())
+        .session_dir(session_dir)
+        .build(common::credential())
+        .unwrap()
+}
+
+/// 上传文件并返回上传结果与收到的事件
+async fn upload_events(
+    client: &Client,
+    video: &Path,
+) -> (ssup::Result<VideoPart>, Vec<UploadEvent>) {
+    let total_size = std::fs::metadata(video).unwrap().len() as usize;
+    let (sx, mut rx) = mpsc::channel(16);
+    let events = tokio::spawn(async move {
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        events
+    });
+    let result = client.upload_video_part(video, total_size, sx, None).await;
+    (result, events.await.unwrap())
+}
+
+/// 上传文件并返回上传结果，同时统计最后一条线路上汇报的进度
+async fn upload(client: &Client, video: &Path) -> (ssup::Result<VideoPart>, usize) {
+    let (result, events) = upload_events(client, video).await;
+    let uploaded = events.iter().fold(0, |uploaded, event| match event {
+        UploadEvent::LineStarted { .. } => 0,
+        UploadEvent::ChunkDone { bytes, .. } => uploaded + bytes,
+        _ => uploaded,
+    });
+    (result, uploaded)
+}
+
+fn video(parts: Vec<VideoPart>) -> Video {
+    Video {
+        copyright: 1,
+        source: "".to_string(),
+        tid: 17,
+        cover: "https://i0.hdslb.com/bfs/archive/mock.jpg".to_string(),
+        title: "测试投稿".to_string(),
+        desc_format_id: 0,
+        desc: "".to_string(),
+        dynamic: "".to_string(),
+        subtitle: Subtitle {
+            open: 0,
+            lan: "".to_string(),
+        },
+        tag: "测试".to_string(),
+        videos: parts,
+        display_time: None,
+        open_subtitle: false,
+    }
+}
+
+#[tokio::test]
+async fn upload_and_submit_by_app() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let client = client(&server, dir.path());
+
+    let (part, uploaded) = upload(&client, &file).await;
+    let part = part.unwrap();
+    assert_eq!(part.filename, "mock");
+    assert_eq!(part.line.as_deref(), Some("bda2"));
+    assert_eq!(uploaded, CHUNK_SIZE * 3 + 100);
+
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2, 3, 4]);
+    let complete = server.requests(Method::POST, UPOS_PATH);
+    assert_eq!(complete.len(), 2);
+    assert_eq!(complete[1].json()["parts"].as_array().unwrap().len(), 4);
+
+    let result = client.submit_by_app(&video(vec![part])).await.unwrap();
+    assert_eq!(result.aid, common::AID);
+    assert_eq!(result.bvid, common::BVID);
+    let submitted = server.requests(Method::POST, "/x/vu/app/add");
+    assert_eq!(submitted.len(), 1);
+    assert_eq!(submitted[0].query["access_key"], "mock_access_token");
+    assert!(submitted[0].query.contains_key("sign"));
+    let body = submitted[0].json();
+    assert_eq!(body["title"], "测试投稿");
+    assert_eq!(body["videos"][0]["filename"], "mock");
+    assert_eq!(body["videos"][0]["title"], "video.mp4");
+}
+
+#[tokio::test]
+async fn resume_after_failed_chunk() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let client = client(&server, dir.path());
+
+    // 4xx 不会被自动重试，上传失败后会话中保留已完成的分块
+    server.fail_chunk(3, 400, 1);
+    let (result, _) = upload(&client, &file).await;
+    assert!(result.is_err());
+    let mut first = server.chunks();
+    first.sort_unstable();
+    assert_eq!(first, vec![1, 2, 3, 4]);
+
+    server.clear();
+    let (part, uploaded) = upload(&client, &file).await;
+    let part = part.unwrap();
+    assert_eq!(part.filename, "mock");
+    assert_eq!(uploaded, CHUNK_SIZE * 3 + 100);
+    // 已完成的分块不再上传，但仍计入整个文件的 MD5
+    let data = std::fs::read(&file).unwrap();
+    assert_eq!(part.md5, Some(common::md5_hex(&data)));
+
+    // 续传时不再创建新的上传，也只上传失败的分块
+    assert_eq!(server.chunks(), vec![3]);
+    let requests = server.requests(Method::POST, UPOS_PATH);
+    assert_eq!(requests.len(), 1);
+    let parts = requests[0].json()["parts"].clone();
+    let numbers: Vec<_> = parts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|part| part["partNumber"].as_u64().unwrap())
+        .collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4]);
+
+    // 上传完成后删除会话文件
+    let sessions = std::fs::read_dir(dir.path())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().ends_with(".upos.json")
+        })
+        .count();
+    assert_eq!(sessions, 0);
+}
+
+#[tokio::test]
+async fn resume_without_reading_completed_chunks() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let total_size = CHUNK_SIZE * 3 + 100;
+    let client = client(&server, dir.path());
+
+    server.fail_chunk(1, 400, 1);
+    assert!(upload(&client, &file).await.0.is_err());
+
+    // 开始续传第 1 个分块后截掉已完成分块所在的部分，读取它们的话上传会失败
+    server.clear();
+    let (sx, mut rx) = mpsc::channel(1);
+    let truncate = async {
+        while let Some(event) = rx.recv().await {
+            if matches!(event, UploadEvent::ChunkStarted { index: 0 }) {
+                let video = std::fs::OpenOptions::new().write(true).open(&file).unwrap();
+                video.set_len(CHUNK_SIZE as u64).unwrap();
+            }
+        }
+    };
+    let (result, _) = tokio::join!(
+        client.upload_video_part(&file, total_size, sx, None),
+        truncate
+    );
+    assert_eq!(result.unwrap().filename, "mock");
+    assert_eq!(server.chunks(), vec![1]);
+}
+
+#[tokio::test]
+async fn restart_rejected_session() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let client = client(&server, dir.path());
+
+    server.fail_chunk(3, 400, 1);
+    let (result, _) = upload(&client, &file).await;
+    assert!(result.is_err());
+
+    // 服务端不再接受原有的 upload_id 时，删除会话并重新上传所有分块
+    server.expire_uploads();
+    server.clear();
+    let (part, uploaded) = upload(&client, &file).await;
+    assert_eq!(part.unwrap().filename, "mock");
+    assert_eq!(uploaded, CHUNK_SIZE * 3 + 100);
+
+    let created: Vec<_> = server
+        .requests(Method::POST, UPOS_PATH)
+        .into_iter()
+        .filter(|req| req.query.contains_key("uploads"))
+        .collect();
+    assert_eq!(created.len(), 1);
+    let mut chunks: Vec<_> = server
+        .requests(Method::PUT, UPOS_PATH)
+        .into_iter()
+        .filter(|req| req.query["uploadId"] == "mock_upload_id_2")
+        .map(|req| req.query["partNumber"].parse::<usize>().unwrap())
+        .collect();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn keep_session_on_auth_error() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let client = client(&server, dir.path());
+
+    server.fail_chunk(3, 400, 1);
+    assert!(upload(&client, &file).await.0.is_err());
+
+    // 续传时的 403 不代表 upload_id 失效，会话应当保留
+    server.fail_chunk(3, 403, 1);
+    assert!(upload(&client, &file).await.0.is_err());
+
+    server.clear();
+    assert!(upload(&client, &file).await.0.is_ok());
+    assert_eq!(server.chunks(), vec![3]);
+}
+
+#[tokio::test]
+async fn discard_stale_session() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 2);
+    let client = client(&server, dir.path());
+    let modify_session = |modify: &dyn Fn(&mut serde_json::Value)| {
+        let session = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().ends_with(".upos.json"))
+            .unwrap();
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&session).unwrap()).unwrap();
+        modify(&mut value);
+        std::fs::write(&session, value.to_string()).unwrap();
+    };
+
+    // 文件大小不变但已被修改
+    server.fail_chunk(2, 400, 1);
+    assert!(upload(&client, &file).await.0.is_err());
+    modify_session(&|session| session["modified"] = 0.into());
+    server.clear();
+    assert!(upload(&client, &file).await.0.is_ok());
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2]);
+
+    // 会话已超过有效期
+    server.fail_chunk(2, 400, 1);
+    assert!(upload(&client, &file).await.0.is_err());
+    modify_session(&|session| session["created"] = 0.into());
+    server.clear();
+    assert!(upload(&client, &file).await.0.is_ok());
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn retry_transient_chunk_error() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 2);
+    let client = client(&server, dir.path());
+
+    server.fail_chunk(2, 503, 1);
+    let (part, events) = upload_events(&client, &file).await;
+    assert_eq!(part.unwrap().filename, "mock");
+    let retries: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            UploadEvent::ChunkRetry { index, attempt, .. } => Some((*index, *attempt)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(retries, vec![(1, 1)]);
+    assert!(matches!(
+        &events[events.len() - 2..],
+        [UploadEvent::Merging, UploadEvent::Finished { filename }] if filename == "mock"
+    ));
+
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2, 2]);
+}
+
+#[tokio::test]
+async fn fail_over_to_next_line() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 2);
+    let client = Client::builder()
+        .config(server.config())
+        .lines([UploadLine::bda2(), UploadLine::ws()])
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    // 第一条线路的分块重试用尽后切换到第二条线路
+    server.fail_chunk(1, 503, 4);
+    let (part, _) = upload(&client, &file).await;
+    assert_eq!(part.unwrap().line.as_deref(), Some("ws"));
+    let lines: Vec<_> = server
+        .requests(Method::GET, "/preupload")
+        .iter()
+        .map(|req| req.query["upcdn"].clone())
+        .collect();
+    assert_eq!(lines, vec!["bda2", "ws"]);
+
+    // 本地文件的错误不会切换线路
+    server.clear();
+    let (sx, _rx) = mpsc::channel(16);
+    let result = client
+        .upload_video_part(&file, CHUNK_SIZE * 3, sx, None)
+        .await;
+    assert!(result.is_err());
+    assert_eq!(server.requests(Method::GET, "/preupload").len(), 1);
+}
+
+#[tokio::test]
+async fn edit_existing_video() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let client = client(&server, dir.path());
+
+    let mut video = client.get_video(&VideoId::AId(common::AID)).await.unwrap();
+    let view = server.requests(Method::GET, "/x/client/archive/view");
+    assert_eq!(view[0].query["aid"], common::AID.to_string());
+    assert_eq!(video.videos.len(), 1);
+    assert_eq!(video.videos[0].cid, Some(100001));
+
+    video.title = "修改后的标题".to_string();
+    client.submit_edit(&video).await.unwrap();
+    let edit = server.requests(Method::POST, "/x/vu/client/edit");
+    assert_eq!(edit[0].query["access_key"], "mock_access_token");
+    assert_eq!(edit[0].json()["title"], "修改后的标题");
+
+    client
+        .edit_card(video.aid, 100001, Vec::new(), false)
+        .await
+        .unwrap();
+    assert_eq!(server.requests(Method::POST, "/x/web/card/submit").len(), 1);
+
+    let cover = common::video_file(dir.path(), "cover.jpg", 1024);
+    let url = client.upload_cover(&cover).await.unwrap();
+    assert_eq!(url, "https://i0.hdslb.com/bfs/archive/mock.jpg");
+}
+
+#[tokio::test]
+async fn probe_lines() {
+    let server = MockServer::start().await;
+    let results = UploadLine::probe(&server.config(), 2, std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].line.name(), "mock");
+    assert_eq!(results[0].failures, 0);
+    assert_eq!(server.requests(Method::GET, "/OK").len(), 2);
+}
+
+#[tokio::test]
+async fn login_with_qrcode() {
+    let server = MockServer::start().await;
+    let config = server.config();
+
+    let qrcode = Credential::get_qrcode(&config).await.unwrap();
+    assert_eq!(qrcode["data"]["auth_code"], "mock_auth_code");
+
+    let credential = Credential::from_qrcode(&config, qrcode).await.unwrap();
+    let polls = server.requests(Method::POST, "/x/passport-tv-login/qrcode/poll");
+    assert_eq!(polls.len(), 2);
+    assert_eq!(credential.get_nickname(&config).await.unwrap(), "mock");
+}
+
+#[tokio::test]
+async fn regenerate_expired_qrcode() {
+    let server = MockServer::start().await;
+    let config = server.config();
+    server.script_polls(&[86090, 86090, 86038]);
+
+    let mut statuses = Vec::new();
+    let credential = Credential::login_by_qrcode(&config, |status| statuses.push(status))
+        .await
+        .unwrap();
+    let url = "https://passport.bilibili.com/x/passport-tv-login/h5/qrcode/auth?auth_code=mock";
+    let generated = QrcodeStatus::Generated {
+        url: url.to_string(),
+    };
+    assert_eq!(
+        statuses,
+        [
+            generated.clone(),
+            QrcodeStatus::Scanned,
+            QrcodeStatus::Expired,
+            generated,
+        ]
+    );
+    let auth_codes = server.requests(Method::POST, "/x/passport-tv-login/qrcode/auth_code");
+    assert_eq!(auth_codes.len(), 2);
+    assert_eq!(credential.get_nickname(&config).await.unwrap(), "mock");
+}
+
+#[tokio::test]
+async fn login_with_web_qrcode() {
+    let server = MockServer::start().await;
+    let config = server.config();
+
+    let mut statuses = Vec::new();
+    let credential = Credential::login_by_web_qrcode(&config, |status| statuses.push(status))
+        .await
+        .unwrap();
+    assert_eq!(statuses.len(), 1);
+    let polls = server.requests(Method::GET, "/x/passport-login/web/qrcode/poll");
+    assert_eq!(polls.len(), 2);
+    assert_eq!(polls[0].query["qrcode_key"], "mock_qrcode_key");
+
+    // 不经过任何 TV 端的接口，只保存 Cookie
+    let tv_requests: Vec<_> = server
+        .all_requests()
+        .into_iter()
+        .filter(|req| req.path.contains("passport-tv-login"))
+        .collect();
+    assert!(tv_requests.is_empty());
+    assert!(!credential.has_access_token());
+    assert_eq!(credential.mid(), 1);
+    assert!(!credential.need_refresh());
+    assert_eq!(credential.get_nickname(&config).await.unwrap(), "mock");
+
+    // 没有 Token 时使用网页端接口投稿与修改稿件
+    let dir = tempfile::tempdir().unwrap();
+    let client = Client::builder()
+        .config(config)
+        .session_dir(dir.path())
+        .build(credential)
+        .unwrap();
+    assert!(matches!(
+        client.submit_by_app(&video(Vec::new())).await,
+        Err(ssup::Error::NoAccessToken)
+    ));
+    let result = client.submit_by_web(&video(Vec::new())).await.unwrap();
+    assert_eq!(result.bvid, common::BVID);
+    let submitted = server.requests(Method::POST, "/x/vu/web/add/v3");
+    assert_eq!(submitted[0].query["csrf"], "mock_csrf");
+    assert_eq!(submitted[0].json()["title"], "测试投稿");
+
+    let video = client
+        .get_video(&VideoId::from_bvid(common::BVID).unwrap())
+        .await
+        .unwrap();
+    client.submit_edit(&video).await.unwrap();
+    let edited = server.requests(Method::POST, "/x/vu/web/edit");
+    assert_eq!(edited.len(), 1);
+    assert_eq!(edited[0].query["csrf"], "mock_csrf");
+    assert!(server
+        .requests(Method::POST, "/x/vu/client/edit")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn login_with_sms() {
+    let server = MockServer::start().await;
+    let config = server.config();
+
+    let sms = Credential::send_sms(&config, 86, "13800000000")
+        .await
+        .unwrap();
+    let error = Credential::from_sms(&config, &sms, "000000")
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ssup::Error::Api { code: 86207, .. }));
+
+    let credential = Credential::from_sms(&config, &sms, "123456").await.unwrap();
+    let login = server.requests(Method::POST, "/x/passport-login/login/sms");
+    let body = String::from_utf8_lossy(&login[1].body).to_string();
+    assert!(body.contains("captcha_key=mock_captcha_key"));
+    assert!(body.contains("tel=13800000000"));
+    assert_eq!(credential.mid(), 1);
+}
+
+#[test]
+fn parse_exported_cookies() {
+    let netscape = "# Netscape HTTP Cookie File\n\
+        .bilibili.com\tTRUE\t/\tFALSE\t0\tDedeUserID\t1\n\
+        #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t0\tSESSDATA\tmock_sessdata\n\
+        .bilibili.com\tTRUE\t/\tFALSE\t0\tbili_jct\tmock_csrf\n\
+        .example.com\tTRUE\t/\tFALSE\t0\tSESSDATA\tother\n";
+    let json = r#"[
+        {"domain": ".bilibili.com", "name": "DedeUserID", "value": "1"},
+        {"domain": ".bilibili.com", "name": "SESSDATA", "value": "mock_sessdata"},
+        {"domain": ".example.com", "name": "SESSDATA", "value": "other"},
+        {"domain": "member.bilibili.com", "name": "bili_jct", "value": "mock_csrf"}
+    ]"#;
+    let header = "Cookie: DedeUserID=1; SESSDATA=mock_sessdata; bili_jct=mock_csrf";
+    for content in [netscape, json, header] {
+        let cookies = CookieInfo::parse(content).unwrap();
+        assert_eq!(
+            cookies.to_string(),
+            "DedeUserID=1; SESSDATA=mock_sessdata; bili_jct=mock_csrf"
+        );
+        cookies.validate().unwrap();
+    }
+
+    let error = CookieInfo::parse("SESSDATA=mock_sessdata")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert_eq!(error.to_string(), "missing cookies: bili_jct, DedeUserID");
+}
+
+#[tokio::test]
+async fn list_archives() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let client = client(&server, dir.path());
+
+    let page = client.list_archives(&[], 1, 2).await.unwrap();
+    assert_eq!(page.total, 3);
+    assert!(page.has_next());
+    let aids: Vec<_> = page.archives.iter().map(|archive| archive.aid).collect();
+    assert_eq!(aids, [common::AID, common::AID + 1]);
+    assert_eq!(page.archives[1].status(), ArchiveStatus::Reviewing);
+
+    let page = client.list_archives(&[], 2, 2).await.unwrap();
+    assert!(!page.has_next());
+    assert_eq!(page.archives.len(), 1);
+
+    let page = client
+        .list_archives(&[ArchiveStatus::Rejected], 1, 10)
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.archives[0].status(), ArchiveStatus::Rejected);
+    assert_eq!(page.archives[0].reject_reason, "标题违规");
+
+    let page = client.list_archives(&[], 3, 2).await.unwrap();
+    assert!(page.archives.is_empty());
+}
+
+#[test]
+fn convert_video_ids() {
+    for (aid, bvid) in [
+        (170001, "BV17x411w7KC"),
+        (2, "BV1xx411c7mD"),
+        (1054803170, "BV1mH4y1u7UA"),
+    ] {
+        assert_eq!(VideoId::AId(aid).bvid(), bvid);
+        assert_eq!(bvid.parse::<VideoId>().unwrap().aid(), Some(aid));
+    }
+    assert_eq!(
+        "bv17x411w7KC".parse::<VideoId>().unwrap().aid(),
+        Some(170001)
+    );
+    assert!("BV17x411w7K".parse::<VideoId>().is_err());
+    assert!("BV17x411w7K0".parse::<VideoId>().is_err());
+    assert_eq!(VideoId::BVId("BV1".into()).aid(), None);
+    assert_eq!(VideoId::BVId("BV17x411w7K0".into()).aid(), None);
+
+    let url: VideoUrl = "https://www.bilibili.com/video/BV17x411w7KC/?p=2&t=10"
+        .parse()
+        .unwrap();
+    assert_eq!(url.id.aid(), Some(170001));
+    assert_eq!(url.part, Some(2));
+    let url: VideoUrl = "m.bilibili.com/video/av170001".parse().unwrap();
+    assert_eq!(url.id.bvid(), "BV17x411w7KC");
+    assert_eq!(url.part, None);
+    assert!("https://space.bilibili.com/1".parse::<VideoUrl>().is_err());
+}
+
+#[tokio::test]
+async fn resolve_short_link() {
+    let server = MockServer::start().await;
+    let config = server.config();
+
+    let url = VideoUrl::resolve(&config, &format!("{}/short", server.url()))
+        .await
+        .unwrap();
+    assert_eq!(url.id.aid(), Some(common::AID));
+    assert_eq!(url.part, Some(2));
+}
+
+#[tokio::test]
+async fn qrcode_login_timeout() {
+    let server = MockServer::start().await;
+    let config = ClientConfig {
+        login_timeout: Some(Duration::from_millis(200)),
+        ..server.config()
+    };
+    server.script_polls(&[86039; 1000]);
+
+    let error = Credential::login_by_qrcode(&config, |_| {})
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ssup::Error::LoginTimeout(_)));
+    let polls = server.requests(Method::POST, "/x/passport-tv-login/qrcode/poll");
+    assert!(!polls.is_empty());
+}
+
+#[tokio::test]
+async fn rate_limited_upload() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 4);
+    let limiter = ssup::RateLimiter::new(Some(CHUNK_SIZE as u64 * 8));
+    let client = Client::builder()
+        .config(server.config())
+        .rate_limiter(limiter.clone())
+        .line(UploadLine::bda2())
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    // 首个分段无需等待，其余 3 个分块需要约 0.375 秒
+    let start = std::time::Instant::now();
+    let (part, uploaded) = upload(&client, &file).await;
+    assert_eq!(part.unwrap().filename, "mock");
+    assert_eq!(uploaded, CHUNK_SIZE * 4);
+    assert!(start.elapsed() >= std::time::Duration::from_millis(300));
+
+    // 取消限速后不再等待
+    limiter.set_rate(None);
+    let start = std::time::Instant::now();
+    let (part, _) = upload(&client, &file).await;
+    assert!(part.is_ok());
+    assert!(start.elapsed() < std::time::Duration::from_millis(300));
+}
+
+/// 使用第三方存储线路上传文件，检查预上传与拉取通知，返回文件内容
+async fn upload_third_party(server: &MockServer, os: &str, size: usize) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", size);
+    let line: UploadLine = serde_json::from_value(serde_json::json!({
+        "os": os,
+        "probe_url": "",
+        "query": "probe_version=20211012",
+    }))
+    .unwrap();
+    let client = Client::builder()
+        .config(server.config())
+        .line(line)
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    let (part, uploaded) = upload(&client, &file).await;
+    let part = part.unwrap();
+    let data = std::fs::read(&file).unwrap();
+    assert_eq!(part.filename, format!("mock_{os}"));
+    assert_eq!(part.line.as_deref(), Some(os));
+    assert_eq!(part.md5, Some(common::md5_hex(&data)));
+    assert_eq!(uploaded, size);
+
+    let preupload = server.requests(Method::GET, "/preupload");
+    assert_eq!(preupload.len(), 1);
+    assert_eq!(preupload[0].query["r"], os);
+    // 上传完成后通知 B 站拉取文件
+    let fetch = server.requests(Method::POST, FETCH_PATH);
+    assert_eq!(fetch.len(), 1);
+    assert_eq!(fetch[0].query["os"], os);
+    assert_eq!(fetch[0].header("X-Upos-Fetch-Source"), format!("mock_{os}"));
+    data
+}
+
+/// 检查分片上传的初始化与各分块请求，返回合并请求
+fn multipart_requests(server: &MockServer, os: &str, chunk_size: usize, data: &[u8]) -> Recorded {
+    let path = format!("/{os}/mock.mp4");
+    let upload_id = format!("mock_{os}_upload");
+    let (init, complete): (Vec<_>, Vec<_>) = server
+        .requests(Method::POST, &path)
+        .into_iter()
+        .partition(|req| req.query.contains_key("uploads"));
+    assert_eq!(init.len(), 1);
+    assert_eq!(init[0].header("Authorization"), "mock_post_auth");
+
+    let mut parts = server.requests(Method::PUT, &path);
+    parts.sort_by_key(|req| req.query["partNumber"].parse::<usize>().unwrap());
+    assert_eq!(parts.len(), 2);
+    for (part, expected) in parts.iter().zip(data.chunks(chunk_size)) {
+        assert_eq!(part.query["uploadId"], upload_id);
+        assert_eq!(part.header("Authorization"), "mock_put_auth");
+        assert!(!part.header("Content-MD5").is_empty());
+        assert!(part.body == expected);
+    }
+
+    assert_eq!(complete.len(), 1);
+    assert_eq!(complete[0].query["uploadId"], upload_id);
+    assert_eq!(complete[0].header("Authorization"), "mock_post_auth");
+    complete[0].clone()
+}
+
+#[tokio::test]
+async fn upload_with_kodo() {
+    let server = MockServer::start().await;
+    // 七牛云的块大小固定为 4M
+    let block_size = 4 * 1024 * 1024;
+    let data = upload_third_party(&server, "kodo", block_size + 100).await;
+
+    for (len, expected) in [
+        (block_size, &data[..block_size]),
+        (100, &data[block_size..]),
+    ] {
+        let blocks = server.requests(Method::POST, &format!("/mkblk/{len}"));
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].header("Authorization"), "UpToken mock_uptoken");
+        assert!(blocks[0].body == expected);
+    }
+    // 按块的顺序合并，文件名为 URL 安全的 Base64 编码
+    let mkfile = server.requests(
+        Method::POST,
+        &format!("/mkfile/{}/key/bW9ja19rZXk=", block_size + 100),
+    );
+    assert_eq!(mkfile.len(), 1);
+    assert_eq!(mkfile[0].header("Authorization"), "UpToken mock_uptoken");
+    assert_eq!(mkfile[0].body, format!("ctx_{block_size},ctx_100"));
+}
+
+#[tokio::test]
+async fn upload_with_bos() {
+    let server = MockServer::start().await;
+    let chunk_size = 10 * 1024 * 1024;
+    let data = upload_third_party(&server, "bos", chunk_size + 100).await;
+
+    let complete = multipart_requests(&server, "bos", chunk_size, &data);
+    assert_eq!(
+        complete.json(),
+        serde_json::json!({
+            "parts": [
+                { "partNumber": 1, "eTag": common::md5_hex(&data[..chunk_size]) },
+                { "partNumber": 2, "eTag": common::md5_hex(&data[chunk_size..]) },
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn upload_with_cos() {
+    let server = MockServer::start().await;
+    let chunk_size = 10 * 1024 * 1024;
+    let data = upload_third_party(&server, "cos", chunk_size + 100).await;
+
+    let complete = multipart_requests(&server, "cos", chunk_size, &data);
+    let expected = format!(
+        "<CompleteMultipartUpload>\
+         <Part><PartNumber>1</PartNumber><ETag>\"{}\"</ETag></Part>\
+         <Part><PartNumber>2</PartNumber><ETag>\"{}\"</ETag></Part>\
+         </CompleteMultipartUpload>",
+        common::md5_hex(&data[..chunk_size]),
+        common::md5_hex(&data[chunk_size..]),
+    );
+    assert_eq!(complete.body, expected);
+}
+
+#[tokio::test]
+async fn upload_with_gcs() {
+    let server = MockServer::start().await;
+    let chunk_size = 8 * 1024 * 1024;
+    let total_size = chunk_size + 100;
+    let data = upload_third_party(&server, "gcs", total_size).await;
+
+    let start = server.requests(Method::POST, "/gcs/mock.mp4");
+    assert_eq!(start.len(), 1);
+    assert_eq!(start[0].header("x-goog-resumable"), "start");
+    // 可续传上传按顺序发送分块
+    let chunks = server.requests(Method::PUT, "/gcs/session");
+    let ranges: Vec<_> = chunks
+        .iter()
+        .map(|req| req.header("Content-Range"))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            format!("bytes 0-{}/{total_size}", chunk_size - 1),
+            format!("bytes {chunk_size}-{}/{total_size}", total_size - 1),
+        ]
+    );
+    assert!(chunks[0].body == data[..chunk_size]);
+    assert!(chunks[1].body == data[chunk_size..]);
+}
+
+#[tokio::test]
+async fn upload_from_reader() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let client = client(&server, dir.path());
+
+    let data = vec![1u8; CHUNK_SIZE * 2 + 100];
+    let (sx, _rx) = mpsc::channel(16);
+    let part = client
+        .upload_video_part_from(&data[..], "live.flv", data.len(), sx, None)
+        .await
+        .unwrap();
+    assert_eq!(part.filename, "mock");
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2, 3]);
+    let preupload = server.requests(Method::GET, "/preupload");
+    assert_eq!(preupload[0].query["name"], "live.flv");
+
+    // 数据源提前结束时上传失败
+    let (sx, _rx) = mpsc::channel(16);
+    let result = client
+        .upload_video_part_from(&data[..], "live.flv", data.len() + 1, sx, None)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn bounded_in_flight_chunks() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 3 + 100);
+    let client = Client::builder()
+        .config(server.config())
+        .concurrency(4)
+        .max_in_flight(CHUNK_SIZE)
+        .line(UploadLine::bda2())
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    // 内存上限只允许一个分块，分块按顺序上传
+    let (part, uploaded) = upload(&client, &file).await;
+    assert_eq!(part.unwrap().filename, "mock");
+    assert_eq!(uploaded, CHUNK_SIZE * 3 + 100);
+    assert_eq!(server.chunks(), vec![1, 2, 3, 4]);
+
+    // 声明的大小与文件不符时上传失败
+    let (sx, _rx) = mpsc::channel(16);
+    let result = client
+        .upload_video_part(&file, CHUNK_SIZE * 3, sx, None)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn verify_chunk_checksums() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = common::video_file(dir.path(), "video.mp4", CHUNK_SIZE * 2 + 100);
+    let client = client(&server, dir.path());
+
+    let (part, _) = upload(&client, &file).await;
+    let data = std::fs::read(&file).unwrap();
+    assert_eq!(part.unwrap().md5, Some(common::md5_hex(&data)));
+
+    // 完成上传时提交服务端返回的 ETag
+    let complete = server.requests(Method::POST, UPOS_PATH);
+    let parts = complete[1].json()["parts"].clone();
+    let mut etags: Vec<_> = parts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|part| part["eTag"].as_str().unwrap().to_string())
+        .collect();
+    etags.sort_unstable();
+    let mut expected: Vec<_> = data.chunks(CHUNK_SIZE).map(common::md5_hex).collect();
+    expected.sort_unstable();
+    assert_eq!(etags, expected);
+
+    // ETag 与分块内容不符时上传失败
+    server.corrupt_chunk(2);
+    let file = common::video_file(dir.path(), "other.mp4", CHUNK_SIZE * 2);
+    let (result, _) = upload(&client, &file).await;
+    assert!(matches!(
+        result,
+        Err(ssup::Error::ChecksumMismatch { part_number: 2, .. })
+    ));
+}
+
+#[tokio::test]
+async fn shared_chunk_budget() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let first = common::video_file(dir.path(), "first.mp4", CHUNK_SIZE * 2);
+    let second = common::video_file(dir.path(), "second.mp4", CHUNK_SIZE * 2);
+    let client = Client::builder()
+        .config(server.config())
+        .concurrency(4)
+        .chunk_budget(ssup::ChunkBudget::new(Some(1)))
+        .line(UploadLine::bda2())
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    // 两个分P同时上传，但同一时间只有一个分块在上传
+    server.delay_chunks(Duration::from_millis(50));
+    let ((first, _), (second, _)) = tokio::join!(upload(&client, &first), upload(&client, &second));
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(server.chunks().len(), 4);
+    assert_eq!(server.max_concurrent_chunks(), 1);
+}
+
+/// 统计已被读取字节数的数据源
+struct CountingReader {
+    data: std::io::Cursor<Vec<u8>>,
+    read: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for CountingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let n = std::io::Read::read(&mut self.data, buf.initialize_unfilled())?;
+        buf.advance(n);
+        self.read.fetch_add(n, Ordering::SeqCst);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn chunk_budget_bounds_reads() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let client = Client::builder()
+        .config(server.config())
+        .concurrency(4)
+        .chunk_budget(ssup::ChunkBudget::new(Some(1)))
+        .line(UploadLine::bda2())
+        .session_dir(dir.path())
+        .build(common::credential())
+        .unwrap();
+
+    // 第一个分块上传期间，数据源中的后续分块不会被读入内存
+    server.delay_chunks(Duration::from_millis(100));
+    let read = Arc::new(AtomicUsize::new(0));
+    let reader = CountingReader {
+        data: std::io::Cursor::new(vec![1u8; CHUNK_SIZE * 3]),
+        read: read.clone(),
+    };
+    let (sx, mut rx) = mpsc::channel(16);
+    let watch = async {
+        let mut read_during_first = None;
+        while let Some(event) = rx.recv().await {
+            if matches!(event, UploadEvent::ChunkStarted { index: 0 }) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                read_during_first = Some(read.load(Ordering::SeqCst));
+            }
+        }
+        read_during_first
+    };
+    let (part, read_during_first) = tokio::join!(
+        client.upload_video_part_from(reader, "live.flv", CHUNK_SIZE * 3, sx, None),
+        watch
+    );
+    assert!(part.is_ok());
+    assert_eq!(read_during_first, Some(CHUNK_SIZE));
+    assert_eq!(read.load(Ordering::SeqCst), CHUNK_SIZE * 3);
+}
+
+#[tokio::test]
+async fn refresh_credential() {
+    let server = MockServer::start().await;
+    let config = server.config();
+
+    let mut credential = common::credential();
+    assert!(credential.refresh(&config, true).await.unwrap());
+    let refreshes = server.requests(Method::POST, "/x/passport-login/oauth2/refresh_token");
+    let form = std::str::from_utf8(&refreshes[0].body).unwrap();
+    assert!(form.contains("refresh_token=mock_refresh_token"));
+    assert!(form.contains("sign="));
+    let json = serde_json::to_value(&credential).unwrap();
+    assert_eq!(json["token_info"]["access_token"], "refreshed_access_token");
+    assert_eq!(
+        json["token_info"]["refresh_token"],
+        "refreshed_refresh_token"
+    );
+
+    // 刷新失败时以 Cookie 重新登录
+    server.fail_refresh();
+    let mut credential = common::credential();
+    assert!(credential.refresh(&config, true).await.unwrap());
+    let confirm = server.requests(Method::POST, "/x/passport-tv-login/h5/qrcode/confirm");
+    assert_eq!(confirm.len(), 1);
+    let json = serde_json::to_value(&credential).unwrap();
+    assert_eq!(json["token_info"]["access_token"], "mock_access_token");
+
+    // 未临近过期时不刷新
+    assert!(!credential.refresh(&config, false).await.unwrap());
+}
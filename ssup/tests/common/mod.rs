@@ -0,0 +1,609 @@
+//! 用于测试的 B 站接口模拟服务
+//!
+//! 实现了投稿流程中用到的接口，所有请求都会被记录下来供测试检查。
+
+// 各个测试只用到其中的一部分
+#![allow(dead_code)]
+
+use hyper::body::Bytes;
+use hyper::header::HOST;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, HeaderMap, Method, Request, Response, Server, StatusCode};
+use md5::Digest;
+use serde_json::{json, Value};
+use ssup::{ClientConfig, Credential};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 模拟上传使用的分块大小
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// 模拟上传的文件路径，对应上传后的文件名为 `mock`
+pub const UPOS_PATH: &str = "/ugcboss/mock.mp4";
+/// 模拟投稿返回的 aid
+pub const AID: u64 = 170001;
+/// 模拟投稿返回的 bvid
+pub const BVID: &str = "BV17x411w7KC";
+/// 第三方存储上传完成后通知 B 站拉取的路径
+pub const FETCH_PATH: &str = "/fetch";
+
+/// 收到的请求
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: Method,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Recorded {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+
+    /// 请求头的值，不存在时返回空字符串
+    pub fn header(&self, name: &str) -> &str {
+        self.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// 注入的分块上传错误
+struct Fault {
+    status: StatusCode,
+    times: usize,
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<Recorded>,
+    faults: HashMap<usize, Fault>,
+    /// 返回错误 ETag 的分块
+    corrupted: HashSet<usize>,
+    /// 分块上传的响应延迟
+    chunk_delay: Duration,
+    /// 正在处理的分块上传请求数
+    active_chunks: usize,
+    max_active_chunks: usize,
+    /// 刷新 Token 是否失败
+    refresh_fails: bool,
+    polls: usize,
+    web_polls: usize,
+    /// 优先返回的二维码轮询结果
+    poll_codes: VecDeque<i64>,
+    /// 已创建的 upos 上传数
+    uploads: usize,
+    /// 已失效的 upos `upload_id`
+    expired_uploads: HashSet<String>,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// 在随机端口上启动服务
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        Self { addr, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 所有接口均指向本服务的客户端配置
+    pub fn config(&self) -> ClientConfig {
+        let url = self.url();
+        ClientConfig {
+            member_url: url.clone(),
+            passport_url: url.clone(),
+            tv_passport_url: url.clone(),
+            api_url: url.clone(),
+            upos_url: Some(url),
+            min_retry_interval: Duration::from_millis(10),
+            max_retry_interval: Duration::from_millis(100),
+            qrcode_poll_interval: Duration::from_millis(10),
+            ..ClientConfig::default()
+        }
+    }
+
+    /// 令第 `part_number` 个分块的前 `times` 次上传返回 `status`
+    pub fn fail_chunk(&self, part_number: usize, status: u16, times: usize) {
+        let status = StatusCode::from_u16(status).unwrap();
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .insert(part_number, Fault { status, times });
+    }
+
+    /// 令第 `part_number` 个分块返回与内容不符的 ETag
+    pub fn corrupt_chunk(&self, part_number: usize) {
+        self.state.lock().unwrap().corrupted.insert(part_number);
+    }
+
+    /// 延迟返回所有分块上传请求
+    pub fn delay_chunks(&self, delay: Duration) {
+        self.state.lock().unwrap().chunk_delay = delay;
+    }
+
+    /// 同时处理的分块上传请求数的最大值
+    pub fn max_concurrent_chunks(&self) -> usize {
+        self.state.lock().unwrap().max_active_chunks
+    }
+
+    /// 令刷新 Token 的请求失败
+    pub fn fail_refresh(&self) {
+        self.state.lock().unwrap().refresh_fails = true;
+    }
+
+    /// 令之后的二维码轮询依次返回 `codes`，之后登录成功
+    pub fn script_polls(&self, codes: &[i64]) {
+        self.state.lock().unwrap().poll_codes.extend(codes);
+    }
+
+    /// 令已创建的 upos 上传全部失效，之后的分块上传与合并返回 404
+    pub fn expire_uploads(&self) {
+        let mut state = self.state.lock().unwrap();
+        let ids: Vec<_> = (1..=state.uploads).map(upload_id).collect();
+        state.expired_uploads.extend(ids);
+    }
+
+    /// 收到的指定方法和路径的请求
+    pub fn requests(&self, method: Method, path: &str) -> Vec<Recorded> {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|req| req.method == method && req.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// 收到的所有请求，按请求顺序排列
+    pub fn all_requests(&self) -> Vec<Recorded> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// 收到的分块上传请求的分块编号，按请求顺序排列
+    pub fn chunks(&self) -> Vec<usize> {
+        self.requests(Method::PUT, UPOS_PATH)
+            .iter()
+            .map(|req| req.query["partNumber"].parse().unwrap())
+            .collect()
+    }
+
+    /// 清空已记录的请求
+    pub fn clear(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+/// 不会过期的登录凭据
+pub fn credential_json() -> Value {
+    json!({
+        "cookie_info": {
+            "cookies": [
+                { "name": "bili_jct", "value": "mock_csrf" },
+                { "name": "SESSDATA", "value": "mock_sessdata" },
+                { "name": "DedeUserID", "value": "1" },
+            ]
+        },
+        "sso": [],
+        "token_info": {
+            "access_token": "mock_access_token",
+            "expires_in": 4_000_000_000u64,
+            "mid": 1,
+            "refresh_token": "mock_refresh_token",
+        }
+    })
+}
+
+pub fn credential() -> Credential {
+    serde_json::from_value(credential_json()).unwrap()
+}
+
+/// 写入指定大小的测试文件
+pub fn video_file(dir: &std::path::Path, name: &str, size: usize) -> std::path::PathBuf {
+    let path = dir.join(name);
+    let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+pub fn md5_hex(data: &[u8]) -> String {
+    format!("{:x}", md5::Md5::digest(data))
+}
+
+fn upload_id(n: usize) -> String {
+    format!("mock_upload_id_{n}")
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (param.to_string(), String::new()),
+        })
+        .collect()
+}
+
+fn json_response(value: Value) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap()
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// 返回内容 MD5 作为 ETag 的分块上传响应
+fn etag_response(body: &[u8]) -> Response<Body> {
+    Response::builder()
+        .header("ETag", format!("\"{}\"", md5_hex(body)))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// 第三方存储线路预上传返回的存储信息
+fn third_party_bucket(os: &str) -> Value {
+    let mut bucket = match os {
+        "kodo" => json!({
+            "endpoint": "//up-mock.qbox.me",
+            "uptoken": "mock_uptoken",
+            "key": "mock_key",
+        }),
+        _ => json!({
+            "url": format!("//{os}-mock.example.com/{os}/mock.mp4"),
+            "post_auth": "mock_post_auth",
+            "put_auth": "mock_put_auth",
+        }),
+    };
+    let fields = bucket.as_object_mut().unwrap();
+    fields.insert("OK".to_string(), json!(1));
+    fields.insert("bili_filename".to_string(), json!(format!("mock_{os}")));
+    fields.insert(
+        "fetch_url".to_string(),
+        json!(format!("//member-mock.bilibili.com{FETCH_PATH}?os={os}")),
+    );
+    fields.insert(
+        "fetch_headers".to_string(),
+        json!({ "X-Upos-Fetch-Source": format!("mock_{os}") }),
+    );
+    bucket
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = parse_query(req.uri().query().unwrap_or_default());
+    let headers = req.headers().clone();
+    let body = hyper::body::to_bytes(req.into_body())
+        .await
+        .unwrap_or_default();
+    state.lock().unwrap().requests.push(Recorded {
+        method: method.clone(),
+        path: path.clone(),
+        query: query.clone(),
+        headers: headers.clone(),
+        body: body.clone(),
+    });
+
+    let response = match (method, path.as_str()) {
+        (Method::GET, "/preupload") if query.get("r").map(String::as_str) == Some("probe") => {
+            json_response(json!({
+                "OK": 1,
+                "lines": [{
+                    "os": "upos",
+                    "probe_url": "//upos-sz-mock.bilivideo.com/OK",
+                    "query": "upcdn=mock&probe_version=20211012",
+                }],
+                "probe": { "get": true },
+            }))
+        }
+        (Method::GET, "/preupload")
+            if matches!(
+                query.get("r").map(String::as_str),
+                Some("kodo" | "bos" | "cos" | "gcs")
+            ) =>
+        {
+            json_response(third_party_bucket(&query["r"]))
+        }
+        (Method::GET, "/preupload") => json_response(json!({
+            "OK": 1,
+            "auth": "mock_auth",
+            "biz_id": 1,
+            "chunk_size": CHUNK_SIZE,
+            "endpoint": "//upos-sz-mock.bilivideo.com",
+            "upos_uri": format!("upos:/{UPOS_PATH}"),
+        })),
+        (Method::GET, "/OK") => Response::new(Body::from("OK")),
+        (Method::POST, UPOS_PATH) if query.contains_key("uploads") => {
+            let mut state = state.lock().unwrap();
+            state.uploads += 1;
+            json_response(json!({
+                "OK": 1,
+                "upload_id": upload_id(state.uploads),
+            }))
+        }
+        (Method::PUT | Method::POST, UPOS_PATH)
+            if query
+                .get("uploadId")
+                .is_some_and(|id| state.lock().unwrap().expired_uploads.contains(id)) =>
+        {
+            status_response(StatusCode::NOT_FOUND)
+        }
+        (Method::PUT, UPOS_PATH) => {
+            let part_number: usize = query
+                .get("partNumber")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_default();
+            let delay = {
+                let mut state = state.lock().unwrap();
+                state.active_chunks += 1;
+                state.max_active_chunks = state.max_active_chunks.max(state.active_chunks);
+                state.chunk_delay
+            };
+            tokio::time::sleep(delay).await;
+            state.lock().unwrap().active_chunks -= 1;
+            let fault = state
+                .lock()
+                .unwrap()
+                .faults
+                .get_mut(&part_number)
+                .filter(|fault| fault.times > 0)
+                .map(|fault| {
+                    fault.times -= 1;
+                    fault.status
+                });
+            match fault {
+                Some(status) => {
+                    // 延迟返回错误，让同时上传的其他分块先完成
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    status_response(status)
+                }
+                None => {
+                    let corrupted = state.lock().unwrap().corrupted.contains(&part_number);
+                    let etag = if corrupted {
+                        md5_hex(b"corrupted")
+                    } else {
+                        md5_hex(&body)
+                    };
+                    Response::builder()
+                        .header("ETag", format!("\"{etag}\""))
+                        .body(Body::from("MULTIPART_PUT_SUCCESS"))
+                        .unwrap()
+                }
+            }
+        }
+        (Method::POST, UPOS_PATH) => json_response(json!({ "OK": 1 })),
+        (Method::POST, FETCH_PATH) => json_response(json!({ "OK": 1 })),
+        (Method::POST, path) if path.starts_with("/mkblk/") => {
+            json_response(json!({ "ctx": format!("ctx_{}", body.len()) }))
+        }
+        (Method::POST, path) if path.starts_with("/mkfile/") => json_response(json!({})),
+        (Method::POST, "/bos/mock.mp4") if query.contains_key("uploads") => {
+            json_response(json!({ "uploadId": "mock_bos_upload" }))
+        }
+        (Method::POST, "/cos/mock.mp4") if query.contains_key("uploads") => Response::new(
+            Body::from("<InitiateMultipartUploadResult><UploadId>mock_cos_upload</UploadId></InitiateMultipartUploadResult>"),
+        ),
+        (Method::PUT, "/bos/mock.mp4" | "/cos/mock.mp4") => etag_response(&body),
+        (Method::POST, "/bos/mock.mp4" | "/cos/mock.mp4") => Response::new(Body::empty()),
+        (Method::POST, "/gcs/mock.mp4") => {
+            let host = headers[HOST].to_str().unwrap();
+            Response::builder()
+                .status(StatusCode::CREATED)
+                .header("Location", format!("http://{host}/gcs/session"))
+                .body(Body::empty())
+                .unwrap()
+        }
+        (Method::PUT, "/gcs/session") => {
+            // 收到最后一个分块前返回 308
+            let range = headers["Content-Range"].to_str().unwrap();
+            let (end, total) = range
+                .trim_start_matches("bytes ")
+                .split_once('-')
+                .and_then(|(_, rest)| rest.split_once('/'))
+                .unwrap();
+            if end.parse::<u64>().unwrap() + 1 == total.parse::<u64>().unwrap() {
+                Response::new(Body::empty())
+            } else {
+                status_response(StatusCode::PERMANENT_REDIRECT)
+            }
+        }
+        (Method::POST, "/x/vu/web/cover/up") => json_response(json!({
+            "code": 0,
+            "data": { "url": "https://i0.hdslb.com/bfs/archive/mock.jpg" },
+        })),
+        (Method::POST, "/x/vu/app/add" | "/x/vu/client/add" | "/x/vu/web/add/v3") => json_response(json!({
+            "code": 0,
+            "data": { "aid": AID, "bvid": BVID },
+        })),
+        (Method::POST, "/x/vu/client/edit" | "/x/vu/web/edit" | "/x/web/card/submit") => {
+            json_response(json!({ "code": 0 }))
+        }
+        (Method::GET, "/x/client/archive/view") => json_response(json!({
+            "code": 0,
+            "data": {
+                "archive": {
+                    "aid": AID,
+                    "copyright": 1,
+                    "source": "",
+                    "tid": 17,
+                    "cover": "https://i0.hdslb.com/bfs/archive/mock.jpg",
+                    "title": "mock",
+                    "desc_format_id": 0,
+                    "desc": "",
+                    "dynamic": "",
+                    "tag": "mock",
+                    "dtime": null,
+                },
+                "videos": [{
+                    "title": "P1",
+                    "filename": "n000001",
+                    "desc": "",
+                    "cid": 100001,
+                    "duration": 60,
+                }],
+            },
+        })),
+        (Method::POST, "/x/passport-tv-login/qrcode/auth_code") => json_response(json!({
+            "code": 0,
+            "data": {
+                "url": "https://passport.bilibili.com/x/passport-tv-login/h5/qrcode/auth?auth_code=mock",
+                "auth_code": "mock_auth_code",
+            },
+        })),
+        (Method::POST, "/x/passport-tv-login/qrcode/poll") => {
+            let (polls, code) = {
+                let mut state = state.lock().unwrap();
+                state.polls += 1;
+                (state.polls, state.poll_codes.pop_front())
+            };
+            if let Some(code) = code {
+                json_response(json!({ "code": code, "message": "" }))
+            } else if polls == 1 {
+                json_response(json!({ "code": 86039, "message": "二维码尚未确认" }))
+            } else {
+                json_response(json!({ "code": 0, "data": credential_json() }))
+            }
+        }
+        (Method::POST, "/x/passport-login/oauth2/refresh_token") => {
+            if state.lock().unwrap().refresh_fails {
+                json_response(json!({ "code": -101, "message": "账号未登录" }))
+            } else {
+                let mut credential = credential_json();
+                credential["token_info"]["access_token"] = json!("refreshed_access_token");
+                credential["token_info"]["refresh_token"] = json!("refreshed_refresh_token");
+                json_response(json!({ "code": 0, "data": credential }))
+            }
+        }
+        (Method::GET, "/x/passport-login/web/qrcode/generate") => json_response(json!({
+            "code": 0,
+            "data": {
+                "url": "https://passport.bilibili.com/h5-app/passport/login/scan?qrcode_key=mock",
+                "qrcode_key": "mock_qrcode_key",
+            },
+        })),
+        (Method::GET, "/x/passport-login/web/qrcode/poll") => {
+            let polls = {
+                let mut state = state.lock().unwrap();
+                state.web_polls += 1;
+                state.web_polls
+            };
+            if polls == 1 {
+                json_response(json!({ "code": 0, "data": { "code": 86101, "message": "未扫码" } }))
+            } else {
+                let mut response = json_response(json!({ "code": 0, "data": { "code": 0 } }));
+                for cookie in [
+                    "SESSDATA=mock_sessdata",
+                    "bili_jct=mock_csrf",
+                    "DedeUserID=1",
+                ] {
+                    response.headers_mut().append(
+                        "Set-Cookie",
+                        format!("{cookie}; Path=/; Domain=bilibili.com")
+                            .parse()
+                            .unwrap(),
+                    );
+                }
+                response
+            }
+        }
+        (Method::POST, "/x/passport-login/sms/send") => json_response(json!({
+            "code": 0,
+            "data": { "captcha_key": "mock_captcha_key", "recaptcha_url": "" },
+        })),
+        (Method::POST, "/x/passport-login/login/sms") => {
+            let form = parse_query(std::str::from_utf8(&body).unwrap_or_default());
+            if form.get("code").map(String::as_str) == Some("123456") {
+                json_response(json!({ "code": 0, "data": credential_json() }))
+            } else {
+                json_response(json!({ "code": 86207, "message": "验证码错误" }))
+            }
+        }
+        (Method::POST, "/x/passport-tv-login/h5/qrcode/confirm") => {
+            json_response(json!({ "code": 0 }))
+        }
+        (Method::GET, "/x/web/archives") => {
+            let archives = [
+                (AID, BVID, "已通过", 0, "开放浏览", ""),
+                (AID + 1, "BV1Cx411w7yB", "审核中", -1, "待审", ""),
+                (AID + 2, "BV1Ux411w7ZM", "未通过", -2, "已退回", "标题违规"),
+            ];
+            let status = query["status"].replace("%2C", ",");
+            let status: Vec<_> = status.split(',').collect();
+            let filtered: Vec<_> = archives
+                .iter()
+                .filter(|(_, _, _, state, _, _)| match state {
+                    0 => status.contains(&"pubed"),
+                    -1 => status.contains(&"is_pubing"),
+                    _ => status.contains(&"not_pubed"),
+                })
+                .map(|(aid, bvid, title, state, state_desc, reject_reason)| {
+                    json!({ "Archive": {
+                        "aid": aid,
+                        "bvid": bvid,
+                        "title": title,
+                        "state": state,
+                        "state_desc": state_desc,
+                        "reject_reason": reject_reason,
+                        "ctime": 1640000000,
+                        "ptime": 1640000000,
+                    }})
+                })
+                .collect();
+            let pn: usize = query["pn"].parse().unwrap();
+            let ps: usize = query["ps"].parse().unwrap();
+            let page: Vec<_> = filtered.iter().skip((pn - 1) * ps).take(ps).collect();
+            json_response(json!({
+                "code": 0,
+                "data": {
+                    "arc_audits": if page.is_empty() { Value::Null } else { json!(page) },
+                    "page": { "pn": pn, "ps": ps, "count": filtered.len() },
+                },
+            }))
+        }
+        (Method::GET, "/short") => Response::builder()
+            .status(StatusCode::FOUND)
+            .header(
+                "Location",
+                format!("https://www.bilibili.com/video/{BVID}/?p=2&share_source=copy"),
+            )
+            .body(Body::empty())
+            .unwrap(),
+        (Method::GET, "/x/web-interface/nav") => json_response(json!({
+            "code": 0,
+            "data": { "uname": "mock" },
+        })),
+        _ => status_response(StatusCode::NOT_FOUND),
+    };
+    Ok(response)
+}
@@ -0,0 +1,220 @@
+use anyhow::{anyhow, bail, Context};
+use parking_lot::Mutex;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use ssup::Credential;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// 读取加密口令的环境变量，用于非交互式运行
+const PASSPHRASE_ENV: &str = "SSWA_PASSPHRASE";
+
+/// PBKDF2-HMAC-SHA256 的迭代次数
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+
+/// 加密后的凭据文件
+#[derive(Serialize, Deserialize)]
+struct Encrypted {
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    /// AES-256-GCM 加密的凭据 JSON
+    ciphertext: String,
+}
+
+/// `accounts` 目录下的帐号凭据，可选加密存储
+///
+/// 加密口令依次从环境变量 `SSWA_PASSPHRASE`、密钥文件和终端输入中获取。
+pub(crate) struct AccountStore {
+    dir: PathBuf,
+    /// 写入新凭据时是否加密
+    encrypt: bool,
+    /// 密钥文件，文件内容整体作为口令
+    key_file: Option<PathBuf>,
+    passphrase: Mutex<Option<Vec<u8>>>,
+}
+
+impl AccountStore {
+    pub(crate) fn new(root: &Path, encrypt: bool, key_file: Option<PathBuf>) -> Self {
+        Self {
+            dir: root.join("accounts"),
+            encrypt,
+            key_file,
+            passphrase: Mutex::new(None),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+
+    pub(crate) fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    /// 所有已保存的帐号名称
+    pub(crate) async fn names(&self) -> anyhow::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(next) = dir.next_entry().await? {
+            let path = next.path();
+            if let (Some("json"), Some(name)) = (
+                path.extension().and_then(|s| s.to_str()),
+                path.file_stem().and_then(|s| s.to_str()),
+            ) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// 凭据文件是否已加密
+    pub(crate) async fn is_encrypted(&self, name: &str) -> anyhow::Result<bool> {
+        let content = fs::read(self.path(name)).await?;
+        let value: serde_json::Value = serde_json::from_slice(&content)?;
+        Ok(value.get("ciphertext").is_some())
+    }
+
+    pub(crate) async fn load(&self, name: &str) -> anyhow::Result<Credential> {
+        let content = fs::read(self.path(name))
+            .await
+            .with_context(|| format!("read account {name}"))?;
+        let value: serde_json::Value = serde_json::from_slice(&content)?;
+        if value.get("ciphertext").is_none() {
+            return Ok(serde_json::from_value(value)?);
+        }
+
+        let encrypted: Encrypted = serde_json::from_value(value)?;
+        let plain = self
+            .decrypt(&encrypted)
+            .with_context(|| format!("decrypt account {name}"))?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+
+    /// 保存凭据，已加密的凭据文件保持加密
+    pub(crate) async fn save(&self, name: &str, credential: &Credential) -> anyhow::Result<()> {
+        let encrypt = self.encrypt || (self.exists(name) && self.is_encrypted(name).await?);
+        self.save_as(name, credential, encrypt).await
+    }
+
+    pub(crate) async fn save_as(
+        &self,
+        name: &str,
+        credential: &Credential,
+        encrypt: bool,
+    ) -> anyhow::Result<()> {
+        let plain = serde_json::to_vec(credential)?;
+        let content = if encrypt {
+            serde_json::to_vec(&self.encrypt(plain)?)?
+        } else {
+            plain
+        };
+        fs::write(self.path(name), content).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn remove(&self, name: &str) -> anyhow::Result<()> {
+        Ok(fs::remove_file(self.path(name)).await?)
+    }
+
+    pub(crate) async fn rename(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        Ok(fs::rename(self.path(from), self.path(to)).await?)
+    }
+
+    /// 获取加密口令，用于加密时终端输入的口令需要输入两次确认
+    fn passphrase(&self, confirm: bool) -> anyhow::Result<Vec<u8>> {
+        let mut cached = self.passphrase.lock();
+        if let Some(passphrase) = cached.as_ref() {
+            return Ok(passphrase.clone());
+        }
+
+        let passphrase = if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            passphrase.into_bytes()
+        } else if let Some(key_file) = &self.key_file {
+            std::fs::read(key_file)
+                .with_context(|| format!("read key file {}", key_file.display()))?
+        } else {
+            let passphrase = prompt_passphrase("请输入帐号凭据的加密口令")?;
+            if confirm && !passphrase.is_empty() {
+                let again = prompt_passphrase("请再次输入加密口令")?;
+                if again != passphrase {
+                    bail!("两次输入的口令不一致！");
+                }
+            }
+            passphrase
+        };
+        if passphrase.is_empty() {
+            bail!("加密口令不能为空！");
+        }
+        *cached = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+
+    fn key(&self, salt: &[u8], iterations: u32, confirm: bool) -> anyhow::Result<LessSafeKey> {
+        let iterations =
+            NonZeroU32::new(iterations).ok_or_else(|| anyhow!("invalid pbkdf2 iterations"))?;
+        let mut key = [0; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            &self.passphrase(confirm)?,
+            &mut key,
+        );
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| anyhow!("invalid key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    fn encrypt(&self, mut data: Vec<u8>) -> anyhow::Result<Encrypted> {
+        let rng = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        rng.fill(&mut salt).map_err(|_| anyhow!("generate salt"))?;
+        rng.fill(&mut nonce)
+            .map_err(|_| anyhow!("generate nonce"))?;
+
+        self.key(&salt, PBKDF2_ITERATIONS, true)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| anyhow!("encrypt credential"))?;
+        Ok(Encrypted {
+            iterations: PBKDF2_ITERATIONS,
+            salt: base64::encode(salt),
+            nonce: base64::encode(nonce),
+            ciphertext: base64::encode(data),
+        })
+    }
+
+    fn decrypt(&self, encrypted: &Encrypted) -> anyhow::Result<Vec<u8>> {
+        let salt = base64::decode(&encrypted.salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(&base64::decode(&encrypted.nonce)?)
+            .map_err(|_| anyhow!("invalid nonce"))?;
+        let mut data = base64::decode(&encrypted.ciphertext)?;
+        let plain = match self.key(&salt, encrypted.iterations, false)?.open_in_place(
+            nonce,
+            Aad::empty(),
+            &mut data,
+        ) {
+            Ok(plain) => plain,
+            Err(_) => {
+                // 不保留错误的口令，避免之后用它加密凭据
+                *self.passphrase.lock() = None;
+                bail!("口令错误或凭据文件已损坏！");
+            }
+        };
+        Ok(plain.to_vec())
+    }
+}
+
+fn prompt_passphrase(message: &str) -> anyhow::Result<Vec<u8>> {
+    let question = requestty::Question::password("passphrase")
+        .message(message)
+        .mask('*')
+        .build();
+    let answer = requestty::prompt_one(question)?;
+    Ok(answer.as_string().unwrap_or_default().as_bytes().to_vec())
+}
@@ -1,19 +1,28 @@
+// clap-handler 按类型注入上下文，处理函数中必须使用 &PathBuf
+#![allow(clippy::ptr_arg)]
+
+use crate::account::AccountStore;
 use crate::config::Config;
 use crate::context::CONTEXT;
 use crate::ffmpeg;
-use crate::template::VideoTemplate;
+use crate::login::{self, LoginMethod};
+use crate::rate::Rate;
+use crate::template::{FieldChange, VideoTemplate};
 use anyhow::{bail, Context};
-use clap::Parser;
+use chrono::{Local, TimeZone};
+use clap::{ArgEnum, Parser};
 use clap_handler::{handler, Context as ClapContext, Handler};
+use futures::{stream, StreamExt, TryStreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rand::Rng;
 use serde_json::Value;
-use ssup::constants::set_useragent;
-use ssup::video::{VideoCardItem, VideoPart};
-use ssup::{Client, CookieEntry, CookieInfo, Credential, VideoId};
+use ssup::video::{ArchiveStatus, VideoCardItem, VideoPart};
+use ssup::{
+    Client, ClientConfig, CookieEntry, CookieInfo, Credential, UploadEvent, VideoId, VideoUrl,
+};
 use std::collections::HashMap;
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use tokio::fs;
 
@@ -60,6 +69,7 @@ impl Handler for Args {
         let _ = fs::create_dir_all(&config_root).await?;
         let _ = fs::create_dir(config_root.join("templates")).await;
         let _ = fs::create_dir(config_root.join("accounts")).await;
+        let _ = fs::create_dir(config_root.join("sessions")).await;
 
         // 初始化读取配置文件
         let config: Config = match fs::read_to_string(config_root.join("config.toml")).await {
@@ -67,12 +77,15 @@ impl Handler for Args {
             Err(_) => Config::new(),
         };
 
+        let mut client_config = config.client_config();
         // 设置 User-Agent
         if let Some(ref user_agent) = self.user_agent {
-            set_useragent(user_agent.to_string());
+            client_config.user_agent = user_agent.to_string();
         }
 
+        ctx.insert(config.accounts(&config_root));
         ctx.insert(config_root);
+        ctx.insert(client_config);
         ctx.insert(config);
         Ok(())
     }
@@ -90,8 +103,12 @@ pub(crate) enum SsCommand {
     Upload(SsUploadCommand),
     /// 增加分P
     Append(SsAppendCommand),
+    /// 按模板修改已投稿视频的信息
+    Edit(SsEditCommand),
     /// 查看已投稿视频
     View(SsViewCommand),
+    /// 列出已投稿视频及其审核状态
+    List(SsListCommand),
     /// 修改视频分段章节
     Card(SsCardCommand),
     /// 帐号登录
@@ -100,6 +117,11 @@ pub(crate) enum SsCommand {
     Logout(SsAccountLogoutCommand),
     /// 列出已登录帐号
     Accounts(SsAccountListCommand),
+    /// 管理已登录帐号
+    #[clap(subcommand)]
+    Account(SsAccountCommand),
+    /// 加密或解密已保存的帐号凭据
+    Encrypt(SsAccountEncryptCommand),
 }
 
 #[derive(Parser, Clone)]
@@ -153,6 +175,10 @@ pub(crate) struct SsUploadCommand {
     #[clap(short, long)]
     names: Vec<String>,
 
+    /// 上传限速，如 `2M` 表示每秒 2MiB，优先于配置文件中的限速
+    #[clap(long)]
+    limit_rate: Option<Rate>,
+
     /// 待投稿的视频
     #[clap(required = true)]
     videos: Vec<PathBuf>,
@@ -160,26 +186,23 @@ pub(crate) struct SsUploadCommand {
 
 /// 尝试导入用户凭据，失败时则以该名称创建新的凭据
 async fn credential(
-    root: &PathBuf,
+    client_config: &ClientConfig,
+    accounts: &AccountStore,
     account: Option<&str>,
     default_user: Option<&str>,
 ) -> anyhow::Result<Credential> {
-    let account_file = root.join("accounts").join(format!(
-        "{}.json",
-        account.or(default_user).expect("account not specified")
-    ));
-    if account_file.exists() {
+    let name = account.or(default_user).expect("account not specified");
+    if accounts.exists(name) {
         // 凭据存在，读取并返回
-        let account = fs::read_to_string(&account_file).await?;
-        let mut account: Credential = serde_json::from_str(&account)?;
+        let mut account = accounts.load(name).await?;
 
         // 自动更新凭据
-        let refreshed = account.refresh(false).await?;
+        let refreshed = account.refresh(client_config, false).await?;
         if refreshed {
-            fs::write(&account_file, serde_json::to_string(&account)?).await?;
+            accounts.save(name, &account).await?;
         }
 
-        if let Ok(nickname) = account.get_nickname().await {
+        if let Ok(nickname) = account.get_nickname(client_config).await {
             eprintln!("投稿用户：{nickname}");
             return Ok(account);
         } else {
@@ -188,176 +211,212 @@ async fn credential(
     }
 
     // 凭据不存在，新登录
-    let qrcode = Credential::get_qrcode().await?;
-    eprintln!(
-        "请打开以下链接登录：\n{}",
-        qrcode["data"]["url"].as_str().unwrap()
-    );
-    let credential = Credential::from_qrcode(qrcode).await?;
-    fs::write(account_file, serde_json::to_string(&credential)?).await?;
+    let credential = login::login(client_config, LoginMethod::Tv, None).await?;
+    accounts.save(name, &credential).await?;
     Ok(credential)
 }
 
+/// 同时上传多个分P，返回的分P与 `videos` 顺序一致
 async fn upload_videos(
     client: &Client,
     progress: &MultiProgress,
     videos: &[(PathBuf, &str)],
+    part_concurrency: usize,
     dry_run: bool,
 ) -> anyhow::Result<Vec<VideoPart>> {
-    let mut parts = Vec::with_capacity(videos.len());
-
+    let mut uploads = Vec::with_capacity(videos.len());
     for (video, video_name) in videos {
-        let metadata = tokio::fs::metadata(&video).await?;
-        let total_size = metadata.len() as usize;
-
-        let p_filename = progress.add(ProgressBar::new_spinner());
-        p_filename.set_message(format!("{}", video.file_name().unwrap().to_string_lossy()));
-        let pb = progress.add(ProgressBar::new(total_size as u64));
-        let format = format!("{{spinner:.green}} [{{wide_bar:.cyan/blue}}] {{bytes}}/{{total_bytes}} ({{bytes_per_sec}}, {{eta}})");
-        pb.set_style(ProgressStyle::default_bar().template(&format)?);
-
-        if dry_run {
-            pb.inc(total_size as u64);
-            pb.finish();
-        } else {
-            pb.set_position(0);
-
-            let (sx, mut rx) = tokio::sync::mpsc::channel(1);
-            let upload = client
-                .upload_video_part(&video, total_size, sx, None /* TODO: Add part name */);
-            tokio::pin!(upload);
-            let result = loop {
-                tokio::select! {
-                    Some(size) = rx.recv() => {
-                        // 上传进度
-                        pb.inc(size as u64);
+        uploads.push(upload_video(client, progress, video, video_name, dry_run));
+    }
+    let parts: Vec<_> = stream::iter(uploads)
+        .buffered(part_concurrency)
+        .try_collect()
+        .await?;
+    Ok(parts.into_iter().flatten().collect())
+}
+
+/// 上传单个分P，`dry_run` 时返回 `None`
+async fn upload_video(
+    client: &Client,
+    progress: &MultiProgress,
+    video: &Path,
+    video_name: &str,
+    dry_run: bool,
+) -> anyhow::Result<Option<VideoPart>> {
+    let metadata = tokio::fs::metadata(&video).await?;
+    let total_size = metadata.len() as usize;
+    let file_name = video.file_name().unwrap().to_string_lossy();
+    let part_name = if video_name.is_empty() {
+        None
+    } else {
+        Some(video_name.to_string())
+    };
+
+    let p_filename = progress.add(ProgressBar::new_spinner());
+    p_filename.set_message(file_name.to_string());
+    let pb = progress.add(ProgressBar::new(total_size as u64));
+    let format =
+        "{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+    pb.set_style(ProgressStyle::default_bar().template(format)?);
+
+    if dry_run {
+        pb.inc(total_size as u64);
+        pb.finish();
+        return Ok(None);
+    }
+    // 失败时再尝试一次，从已保存的会话继续上传
+    let mut retry = 1;
+    let part = loop {
+        pb.set_position(0);
+
+        let (sx, mut rx) = tokio::sync::mpsc::channel(1);
+        let upload = client.upload_video_part(&video, total_size, sx, part_name.clone());
+        tokio::pin!(upload);
+        let result = loop {
+            tokio::select! {
+                Some(event) = rx.recv() => match event {
+                    UploadEvent::LineStarted { line } => {
+                        // 切换线路后重新上传
+                        pb.set_position(0);
+                        p_filename.set_message(format!("{file_name}（线路：{line}）"));
                     }
-                    video = &mut upload => {
-                        // 上传完成
-                        p_filename.finish();
-                        pb.finish();
-                        break video;
+                    UploadEvent::ChunkDone { bytes, .. } => pb.inc(bytes as u64),
+                    UploadEvent::ChunkRetry { index, attempt, error } => {
+                        progress.println(format!(
+                            "{file_name} 第 {} 个分块上传失败：{error}，正在第 {attempt} 次重试",
+                            index + 1
+                        ))?;
                     }
-                }
-            };
-            if let Ok(part) = result {
-                parts.push(part);
-            } else {
-                // once again
-                pb.set_position(0);
-
-                let (sx, mut rx) = tokio::sync::mpsc::channel(1);
-                let upload = client.upload_video_part(
-                    &video,
-                    total_size,
-                    sx,
-                    if video_name.is_empty() {
-                        None
-                    } else {
-                        Some(video_name.to_string())
-                    },
-                );
-                tokio::pin!(upload);
-                loop {
-                    tokio::select! {
-                        Some(size) = rx.recv() => {
-                            // 上传进度
-                            pb.inc(size as u64);
-                        }
-                        video = &mut upload => {
-                            // 上传完成
-                            p_filename.finish();
-                            pb.finish();
-                            parts.push(video?);
-                            break;
-                        }
+                    UploadEvent::Merging => {
+                        p_filename.set_message(format!("{file_name}（合并中）"));
                     }
+                    _ => {}
+                },
+                video = &mut upload => {
+                    break video;
                 }
             }
+        };
+        match result {
+            Ok(part) => break part,
+            Err(err) if retry > 0 => {
+                retry -= 1;
+                progress.println(format!("{file_name} 上传失败：{err:#}，正在重试"))?;
+            }
+            Err(err) => return Err(err.into()),
         }
+    };
+
+    // 上传完成
+    if let Some(md5) = &part.md5 {
+        log::info!("Uploaded {file_name} as {}, md5: {md5}", part.filename);
+    }
+    if let Some(line) = &part.line {
+        p_filename.finish_with_message(format!("{file_name}（线路：{line}）"));
+    } else {
+        p_filename.finish();
     }
+    pb.finish();
 
-    Ok(parts)
+    Ok(Some(part))
 }
 
 impl SsUploadCommand {
     /// 尝试导入视频模板
-    async fn template(&self, root: &PathBuf) -> anyhow::Result<VideoTemplate> {
-        fn set_variable<I>(key: &str, value: I)
-        where
-            I: Into<Value>,
-        {
-            if key.starts_with('$') || key.starts_with("ss_") {
-                eprintln!("跳过变量：{key}");
-            } else {
-                CONTEXT.insert(key.to_string(), value);
-            }
-        }
+    async fn template(&self, root: &Path) -> anyhow::Result<VideoTemplate> {
+        load_template(
+            root,
+            &self.template,
+            self.variable_file.as_deref(),
+            &self.variables,
+            self.keep_quote_pairs,
+        )
+        .await
+    }
+}
 
-        let template = root
-            .join("templates")
-            .join(format!("{}.toml", self.template));
-        if !template.exists() {
-            bail!("Template not found!");
+/// 导入视频模板，并依次设置变量文件与命令行中的变量
+async fn load_template(
+    root: &Path,
+    name: &str,
+    variable_file: Option<&Path>,
+    variables: &[String],
+    keep_quote_pairs: bool,
+) -> anyhow::Result<VideoTemplate> {
+    fn set_variable<I>(key: &str, value: I)
+    where
+        I: Into<Value>,
+    {
+        if key.starts_with('$') || key.starts_with("ss_") {
+            eprintln!("跳过变量：{key}");
+        } else {
+            CONTEXT.insert(key.to_string(), value);
         }
+    }
 
-        let template = fs::read_to_string(template).await?;
-        let template: VideoTemplate = toml::from_str(&template)?;
-        for (variable, detail) in template.variables.iter() {
-            if detail.can_skip && detail.default.is_none() {
-                set_variable(variable, String::new());
-            }
+    let template = root.join("templates").join(format!("{name}.toml"));
+    if !template.exists() {
+        bail!("Template not found!");
+    }
+
+    let template = fs::read_to_string(template).await?;
+    let template: VideoTemplate = toml::from_str(&template)?;
+    for (variable, detail) in template.variables.iter() {
+        if detail.can_skip && detail.default.is_none() {
+            set_variable(variable, String::new());
         }
+    }
 
-        // 低优先级：变量文件
-        if let Some(variables) = &self.variable_file {
-            let file = fs::read_to_string(variables).await?;
-            if file.starts_with('{') {
-                // parse as json file
-                let json: HashMap<String, Value> = serde_json::from_str(&file)?;
-                for (key, value) in json {
-                    set_variable(&key, value);
-                }
-            } else {
-                for mut line in file.split('\n') {
-                    line = line.trim();
-                    if !line.is_empty() && !line.starts_with('#') {
-                        let (key, value) = line.split_once('=').unwrap_or((&line, ""));
-                        let key = key.trim();
-                        let mut value = value.trim_matches(' ');
-                        if !self.keep_quote_pairs
-                            && ((value.starts_with('"') && value.ends_with('"'))
-                                || (value.starts_with('\'') && value.ends_with('\'')))
-                        {
-                            value = &value[1..value.len() - 1];
-                        }
-                        let value = value.replace("\\n", "\n");
-                        set_variable(key, value);
+    // 低优先级：变量文件
+    if let Some(variables) = variable_file {
+        let file = fs::read_to_string(variables).await?;
+        if file.starts_with('{') {
+            // parse as json file
+            let json: HashMap<String, Value> = serde_json::from_str(&file)?;
+            for (key, value) in json {
+                set_variable(&key, value);
+            }
+        } else {
+            for mut line in file.split('\n') {
+                line = line.trim();
+                if !line.is_empty() && !line.starts_with('#') {
+                    let (key, value) = line.split_once('=').unwrap_or((line, ""));
+                    let key = key.trim();
+                    let mut value = value.trim_matches(' ');
+                    if !keep_quote_pairs
+                        && ((value.starts_with('"') && value.ends_with('"'))
+                            || (value.starts_with('\'') && value.ends_with('\'')))
+                    {
+                        value = &value[1..value.len() - 1];
                     }
+                    let value = value.replace("\\n", "\n");
+                    set_variable(key, value);
                 }
             }
         }
-        // 高优先级：命令行变量
-        for variable in self.variables.iter() {
-            let (key, value) = variable.split_once('=').unwrap_or((&variable, ""));
-            set_variable(key.trim(), value.trim());
-        }
-
-        Ok(template)
     }
+    // 高优先级：命令行变量
+    for variable in variables.iter() {
+        let (key, value) = variable.split_once('=').unwrap_or((variable, ""));
+        set_variable(key.trim(), value.trim());
+    }
+
+    Ok(template)
 }
 
 #[handler(SsUploadCommand)]
 async fn handle_upload(
     this: &SsUploadCommand,
     config_root: &PathBuf,
+    accounts: &AccountStore,
     config: &Config,
+    client_config: &ClientConfig,
     args: &Args,
 ) -> anyhow::Result<()> {
     let progress = indicatif::MultiProgress::new();
 
     // 加载模板
-    let template = this.template(&config_root).await?;
+    let template = this.template(config_root).await?;
 
     // 预定义变量
     CONTEXT.insert_sys("config_root".to_string(), config_root.to_string_lossy());
@@ -390,7 +449,8 @@ async fn handle_upload(
 
     // 用户登录检查
     let credential = credential(
-        config_root,
+        client_config,
+        accounts,
         args.account.as_deref(),
         template
             .default_user
@@ -400,10 +460,17 @@ async fn handle_upload(
     .await?;
 
     // 线路选择
+    config.limit_rate(client_config, this.limit_rate);
     let client = {
-        let line = config.line().await?;
-        progress.println(format!("已选择线路：{}", line.probe_url()))?;
-        Client::new(line, credential)
+        let mut lines = config.lines(client_config, &progress).await?;
+        let line = lines.remove(0);
+        progress.println(format!("已选择线路：{}", line.name()))?;
+        Client::builder()
+            .config(client_config.clone())
+            .line(line)
+            .lines(lines)
+            .session_dir(config_root.join("sessions"))
+            .build(credential)?
     };
 
     // 上传封面
@@ -460,20 +527,34 @@ async fn handle_upload(
     }
 
     // 上传分P
-    let parts = upload_videos(&client, &progress, &video_files, this.dry_run).await?;
+    let parts = upload_videos(
+        &client,
+        &progress,
+        &video_files,
+        config.part_concurrency(),
+        this.dry_run,
+    )
+    .await?;
 
     // 提交视频
     let video = template.to_video(&tmpl, parts, cover)?;
     if !this.dry_run {
         let mut retry = config.submit_retry();
         loop {
-            match client.submit_by_app(&video).await {
-                Ok(_) => {
+            // 网页端扫码登录的帐号没有 Token，只能使用网页端接口投稿
+            let result = if client.has_access_token() {
+                client.submit_by_app(&video).await
+            } else {
+                client.submit_by_web(&video).await
+            };
+            match result {
+                Ok(result) => {
                     eprintln!("投稿成功！");
+                    println!("{} (av{})", result.bvid, result.aid);
                     break;
                 }
                 Err(err) => {
-                    if retry == 0 {
+                    if retry == 0 || !err.is_retryable() {
                         bail!("投稿失败：{}", err);
                     } else {
                         println!("投稿失败：{}", err);
@@ -490,9 +571,9 @@ async fn handle_upload(
 
 #[derive(Parser, Clone)]
 pub(crate) struct SsAppendCommand {
-    /// 待增加分P的视频 ID
+    /// 待增加分P的视频 ID 或链接
     #[clap(short = 'v', long)]
-    video_id: VideoId,
+    video_id: String,
 
     /// 视频分p标题
     /// 当为空时自动选取视频文件名作为标题
@@ -500,6 +581,10 @@ pub(crate) struct SsAppendCommand {
     #[clap(short, long)]
     names: Vec<String>,
 
+    /// 上传限速，如 `2M` 表示每秒 2MiB，优先于配置文件中的限速
+    #[clap(long)]
+    limit_rate: Option<Rate>,
+
     /// 添加的视频
     #[clap(required = true)]
     videos: Vec<PathBuf>,
@@ -509,19 +594,29 @@ pub(crate) struct SsAppendCommand {
 async fn handle_append(
     this: &SsAppendCommand,
     config_root: &PathBuf,
+    accounts: &AccountStore,
     config: &Config,
+    client_config: &ClientConfig,
     args: &Args,
 ) -> anyhow::Result<()> {
     // 1. 获取待修改视频
     let credential = credential(
-        config_root,
+        client_config,
+        accounts,
         args.account.as_deref(),
         config.default_user.as_deref(),
     )
     .await?;
-    let line = config.line().await?;
-    let client = Client::new(line, credential);
-    let mut video = client.get_video(&this.video_id).await?;
+    let progress = indicatif::MultiProgress::new();
+    let lines = config.lines(client_config, &progress).await?;
+    config.limit_rate(client_config, this.limit_rate);
+    let client = Client::builder()
+        .config(client_config.clone())
+        .lines(lines)
+        .session_dir(config_root.join("sessions"))
+        .build(credential)?;
+    let video_url = VideoUrl::resolve(client_config, &this.video_id).await?;
+    let mut video = client.get_video(&video_url.id).await?;
 
     // 2. 检查文件存在
     for video in this.videos.iter() {
@@ -530,10 +625,7 @@ async fn handle_append(
         }
     }
 
-    // 3. 准备进度条
-    let progress = indicatif::MultiProgress::new();
-
-    // 4. 准备文件名
+    // 3. 准备文件名
     let videos: Vec<_> = this
         .videos
         .iter()
@@ -546,15 +638,21 @@ async fn handle_append(
         })
         .collect();
 
-    // 5. 上传分P
-    let mut parts = upload_videos(&client, &progress, &videos, false)
-        .await?
-        .into_iter()
-        .map(|p| p.into())
-        .collect();
+    // 4. 上传分P
+    let mut parts = upload_videos(
+        &client,
+        &progress,
+        &videos,
+        config.part_concurrency(),
+        false,
+    )
+    .await?
+    .into_iter()
+    .map(|p| p.into())
+    .collect();
     video.videos.append(&mut parts);
 
-    // 6. 提交视频
+    // 5. 提交视频
     eprintln!("准备投稿…");
     let mut retry = config.submit_retry();
     loop {
@@ -564,7 +662,7 @@ async fn handle_append(
                 break;
             }
             Err(err) => {
-                if retry == 0 {
+                if retry == 0 || !err.is_retryable() {
                     bail!("投稿失败：{}", err);
                 } else {
                     println!("投稿失败：{}", err);
@@ -579,45 +677,315 @@ async fn handle_append(
     Ok(())
 }
 
+#[derive(Parser, Clone)]
+pub(crate) struct SsEditCommand {
+    /// 待修改的视频 ID 或链接
+    #[clap(short = 'v', long)]
+    video_id: String,
+
+    /// 修改使用的模板
+    #[clap(short, long)]
+    template: String,
+
+    /// 模板对应的变量
+    #[clap(long = "var")]
+    variables: Vec<String>,
+
+    /// 变量文件
+    #[clap(short = 'f', long = "variable-file")]
+    variable_file: Option<PathBuf>,
+
+    /// 检查的等级
+    ///
+    /// 出现1次：跳过修改确认
+    /// 出现2次：跳过变量输入，当存在必填变量时会产生错误
+    /// 出现3次：跳过所有变量输入且不产生错误
+    #[clap(short = 'y', parse(from_occurrences))]
+    skip_level: u8,
+
+    /// 使用模板中的封面替换原封面
+    #[clap(long)]
+    cover: bool,
+
+    /// 是否自动缩放封面到 960*600
+    #[clap(long)]
+    scale_cover: Option<bool>,
+
+    /// 是否保留简单变量文件中值前后的引号（包括单引号和双引号）
+    #[clap(short = 'q', long = "quotes")]
+    keep_quote_pairs: bool,
+
+    /// 是否模拟修改
+    ///
+    /// 模拟修改时，只显示变更内容，不会实际上传封面和提交
+    #[clap(short, long)]
+    dry_run: bool,
+}
+
+#[handler(SsEditCommand)]
+async fn handle_edit(
+    this: &SsEditCommand,
+    config_root: &PathBuf,
+    accounts: &AccountStore,
+    config: &Config,
+    client_config: &ClientConfig,
+    args: &Args,
+) -> anyhow::Result<()> {
+    // 1. 加载模板
+    let template = load_template(
+        config_root,
+        &this.template,
+        this.variable_file.as_deref(),
+        &this.variables,
+        this.keep_quote_pairs,
+    )
+    .await?;
+
+    // 2. 获取待修改视频
+    let credential = credential(
+        client_config,
+        accounts,
+        args.account.as_deref(),
+        template
+            .default_user
+            .as_deref()
+            .or(config.default_user.as_deref()),
+    )
+    .await?;
+    let client = Client::builder()
+        .config(client_config.clone())
+        .build(credential)?;
+    let video_url = VideoUrl::resolve(client_config, &this.video_id).await?;
+    let mut video = client.get_video(&video_url.id).await?;
+
+    // 3. 编译模板
+    CONTEXT.insert_sys("config_root".to_string(), config_root.to_string_lossy());
+    CONTEXT.insert_sys("aid".to_string(), video.aid);
+    CONTEXT.insert_sys("bvid".to_string(), VideoId::AId(video.aid).bvid());
+    let tmpl = template
+        .build(this.skip_level)
+        .with_context(|| "build template")?;
+
+    // 模板变量检查，修改内容在比较变更后统一确认
+    template
+        .validate(&tmpl, this.skip_level.max(1))
+        .with_context(|| "validate template")?;
+
+    // 4. 比较变更，仅在指定 `--cover` 时替换封面
+    let mut changes = template.update(&tmpl, &mut video)?;
+    let cover = if !this.cover {
+        None
+    } else if template.auto_cover() {
+        bail!("模板中未指定封面文件！");
+    } else {
+        let cover = template.cover(&tmpl)?;
+        changes.push(FieldChange {
+            name: "封面",
+            old: video.cover.clone(),
+            new: cover.clone(),
+        });
+        Some(cover)
+    };
+    if changes.is_empty() {
+        eprintln!("稿件信息没有变化！");
+        return Ok(());
+    }
+    for change in changes.iter() {
+        eprint!("{change}");
+    }
+    if this.dry_run {
+        return Ok(());
+    }
+    if this.skip_level == 0 {
+        let question = requestty::Question::confirm("anonymous")
+            .message("修改内容如上，是否提交？")
+            .build();
+        let confirm = requestty::prompt_one(question)?;
+        if !confirm.as_bool().unwrap_or(false) {
+            return Ok(());
+        }
+    }
+
+    // 5. 上传封面
+    if let Some(cover) = cover {
+        let scaled = if this.scale_cover.unwrap_or(config.need_scale_cover()) {
+            Some(ffmpeg::scale_cover(&cover).with_context(|| "ffmpeg::scale_cover")?)
+        } else {
+            None
+        };
+        let cover_path = match &scaled {
+            Some(scaled) => scaled.to_path_buf(),
+            None => cover.into(),
+        };
+        video.cover = client
+            .upload_cover(cover_path)
+            .await
+            .with_context(|| "upload cover")?;
+        eprintln!("封面已上传！");
+    }
+
+    // 6. 提交修改
+    let mut retry = config.submit_retry();
+    loop {
+        match client.submit_edit(&video).await {
+            Ok(_) => {
+                eprintln!("修改成功！");
+                break;
+            }
+            Err(err) => {
+                if retry == 0 || !err.is_retryable() {
+                    bail!("修改失败：{}", err);
+                } else {
+                    println!("修改失败：{}", err);
+                    retry -= 1;
+                    println!("正在重试，剩余 {} 次", retry);
+                    std::thread::sleep(std::time::Duration::from_secs(3));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Parser, Clone)]
 pub(crate) struct SsViewCommand {
     /// 使用的帐号
     #[clap(short = 'u', long = "user")]
     account: Option<String>,
 
-    /// 查看的视频 ID
-    video_id: VideoId,
+    /// 查看的视频 ID 或链接
+    video_id: String,
 }
 
 #[handler(SsViewCommand)]
 async fn handle_view(
     this: &SsViewCommand,
-    config_root: &PathBuf,
+    accounts: &AccountStore,
     config: &Config,
+    client_config: &ClientConfig,
 ) -> anyhow::Result<()> {
     let credential = credential(
-        config_root,
+        client_config,
+        accounts,
         this.account.as_deref(),
         config.default_user.as_deref(),
     )
     .await?;
-    let client = Client::auto(credential).await?;
-    let video = client.get_video(&this.video_id).await?;
+    let client = Client::builder()
+        .config(client_config.clone())
+        .build(credential)?;
+    let video_url = VideoUrl::resolve(client_config, &this.video_id).await?;
+    let video = client.get_video(&video_url.id).await?;
     println!("{:#?}", video);
     Ok(())
 }
 
+/// 稿件状态
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub(crate) enum ListStatus {
+    /// 已通过
+    Published,
+    /// 审核中
+    Reviewing,
+    /// 未通过
+    Rejected,
+}
+
+impl From<ListStatus> for ArchiveStatus {
+    fn from(status: ListStatus) -> Self {
+        match status {
+            ListStatus::Published => ArchiveStatus::Published,
+            ListStatus::Reviewing => ArchiveStatus::Reviewing,
+            ListStatus::Rejected => ArchiveStatus::Rejected,
+        }
+    }
+}
+
+#[derive(Parser, Clone)]
+pub(crate) struct SsListCommand {
+    /// 按稿件状态筛选，可指定多个，留空时列出所有稿件
+    #[clap(short, long, arg_enum)]
+    status: Vec<ListStatus>,
+
+    /// 从 1 开始的页码
+    #[clap(short, long, default_value = "1")]
+    page: u32,
+
+    /// 每页的稿件数
+    #[clap(long, default_value = "20")]
+    page_size: u32,
+
+    /// 从 `page` 开始列出所有稿件
+    #[clap(short, long)]
+    all: bool,
+
+    /// 以 JSON 格式输出
+    #[clap(long)]
+    json: bool,
+}
+
+#[handler(SsListCommand)]
+async fn handle_list(
+    this: &SsListCommand,
+    accounts: &AccountStore,
+    config: &Config,
+    client_config: &ClientConfig,
+    args: &Args,
+) -> anyhow::Result<()> {
+    let credential = credential(
+        client_config,
+        accounts,
+        args.account.as_deref(),
+        config.default_user.as_deref(),
+    )
+    .await?;
+    let client = Client::builder()
+        .config(client_config.clone())
+        .build(credential)?;
+
+    let status: Vec<ArchiveStatus> = this.status.iter().map(|&s| s.into()).collect();
+    let mut archives = Vec::new();
+    let mut page = this.page.max(1);
+    loop {
+        let result = client.list_archives(&status, page, this.page_size).await?;
+        archives.extend(result.archives.iter().cloned());
+        if !this.all || !result.has_next() || result.archives.is_empty() {
+            break;
+        }
+        page += 1;
+    }
+
+    if this.json {
+        println!("{}", serde_json::to_string_pretty(&archives)?);
+        return Ok(());
+    }
+    println!("aid\tbvid\t状态\t标题\t退回原因");
+    for archive in archives {
+        let status = match archive.status() {
+            ArchiveStatus::Published => "已通过",
+            ArchiveStatus::Reviewing => "审核中",
+            ArchiveStatus::Rejected => "未通过",
+        };
+        println!(
+            "{}\t{}\t{status}（{}）\t{}\t{}",
+            archive.aid, archive.bvid, archive.state_desc, archive.title, archive.reject_reason
+        );
+    }
+    Ok(())
+}
+
 #[derive(Parser, Clone)]
 pub(crate) struct SsCardCommand {
     /// 使用的帐号
     #[clap(short = 'u', long = "user")]
     account: Option<String>,
 
-    /// 视频 ID
+    /// 视频 ID 或链接
     #[clap(short, long)]
-    video_id: VideoId,
+    video_id: String,
 
-    /// 视频分P编号
+    /// 视频分P编号，留空时使用链接中的分P
     #[clap(short, long = "part")]
     part_id: Option<usize>,
 
@@ -636,8 +1004,9 @@ pub(crate) struct SsCardCommand {
 #[handler(SsCardCommand)]
 async fn handle_card(
     this: &SsCardCommand,
-    config_root: &PathBuf,
+    accounts: &AccountStore,
     config: &Config,
+    client_config: &ClientConfig,
 ) -> anyhow::Result<()> {
     fn parse_time_point(input: &str) -> Result<u64, ParseIntError> {
         if input.contains(':') {
@@ -655,7 +1024,6 @@ async fn handle_card(
     let data = fs::read_to_string(&this.card_file).await?;
     let time_points: Vec<(u64, &str)> = data
         .split('\n')
-        .into_iter()
         .filter_map(|line| {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
@@ -676,15 +1044,19 @@ async fn handle_card(
 
     // get video info
     let credential = credential(
-        config_root,
+        client_config,
+        accounts,
         this.account.as_deref(),
         config.default_user.as_deref(),
     )
     .await?;
-    let client = Client::auto(credential).await?;
-    let video = client.get_video(&this.video_id).await?;
+    let client = Client::builder()
+        .config(client_config.clone())
+        .build(credential)?;
+    let video_url = VideoUrl::resolve(client_config, &this.video_id).await?;
+    let video = client.get_video(&video_url.id).await?;
 
-    let part_index = match this.part_id {
+    let part_index = match this.part_id.or(video_url.part) {
         None => 0,
         Some(0) => 0,
         Some(i) => i - 1,
@@ -720,57 +1092,181 @@ async fn handle_card(
 }
 
 #[derive(Parser, Clone)]
-pub(crate) struct SsAccountListCommand;
+pub(crate) struct SsAccountListCommand {
+    /// 显示帐号的昵称、登录时间与过期时间，并检查登录是否有效
+    #[clap(short, long)]
+    long: bool,
+}
 
 #[handler(SsAccountListCommand)]
-async fn account_list(config_root: &PathBuf) -> anyhow::Result<()> {
-    let accounts = config_root.join("accounts");
-    let mut dir = fs::read_dir(accounts).await?;
-    while let Some(next) = dir.next_entry().await? {
-        if let Some("json") = next.path().extension().map(|s| s.to_str().unwrap()) {
-            println!("{}", next.path().file_stem().unwrap().to_string_lossy());
+async fn account_list(
+    this: &SsAccountListCommand,
+    accounts: &AccountStore,
+    client_config: &ClientConfig,
+) -> anyhow::Result<()> {
+    if !this.long {
+        for name in accounts.names().await? {
+            println!("{name}");
         }
+        return Ok(());
+    }
+
+    println!("帐号\t昵称\tmid\t登录时间\t过期时间\t状态");
+    for name in accounts.names().await? {
+        let account = match accounts.load(&name).await {
+            Ok(account) => account,
+            Err(e) => {
+                println!("{name}\t-\t-\t-\t-\t读取失败：{e:#}");
+                continue;
+            }
+        };
+        let nickname = account.get_nickname(client_config).await;
+        let status = if nickname.is_err() {
+            "已失效"
+        } else if account.need_refresh() {
+            "即将过期"
+        } else {
+            "有效"
+        };
+        println!(
+            "{name}\t{}\t{}\t{}\t{}\t{status}",
+            nickname.as_deref().unwrap_or("-"),
+            account.mid(),
+            format_timestamp(account.login_time()),
+            format_timestamp(account.expires_at()),
+        );
     }
 
     Ok(())
 }
 
+/// 以本地时间格式化 Unix 时间戳，未知的时间显示为 `-`
+fn format_timestamp(timestamp: u64) -> String {
+    match Local.timestamp_opt(timestamp as i64, 0).single() {
+        Some(time) if timestamp > 0 => time.format("%Y-%m-%d %H:%M").to_string(),
+        _ => "-".to_string(),
+    }
+}
+
+#[derive(Parser, Handler, Clone)]
+pub(crate) enum SsAccountCommand {
+    /// 强制刷新帐号凭据
+    Refresh(SsAccountRefreshCommand),
+    /// 重命名帐号
+    Rename(SsAccountRenameCommand),
+}
+
+#[derive(Parser, Clone)]
+pub(crate) struct SsAccountRefreshCommand {
+    /// 待刷新凭据的帐号名称
+    name: String,
+}
+
+#[handler(SsAccountRefreshCommand)]
+async fn account_refresh(
+    this: &SsAccountRefreshCommand,
+    accounts: &AccountStore,
+    client_config: &ClientConfig,
+) -> anyhow::Result<()> {
+    if !accounts.exists(&this.name) {
+        bail!("帐号 {} 不存在！", this.name);
+    }
+
+    let mut account = accounts.load(&this.name).await?;
+    if !account.has_access_token() {
+        bail!(
+            "帐号 {} 由网页端扫码登录，无法刷新凭据，请重新登录！",
+            this.name
+        );
+    }
+    account.refresh(client_config, true).await?;
+    accounts.save(&this.name, &account).await?;
+    eprintln!(
+        "帐号 {} 已刷新！凭据将于 {} 过期",
+        this.name,
+        format_timestamp(account.expires_at())
+    );
+    Ok(())
+}
+
+#[derive(Parser, Clone)]
+pub(crate) struct SsAccountRenameCommand {
+    /// 原帐号名称
+    from: String,
+    /// 新帐号名称
+    to: String,
+}
+
+#[handler(SsAccountRenameCommand)]
+async fn account_rename(
+    this: &SsAccountRenameCommand,
+    accounts: &AccountStore,
+) -> anyhow::Result<()> {
+    if !accounts.exists(&this.from) {
+        bail!("帐号 {} 不存在！", this.from);
+    }
+    if accounts.exists(&this.to) {
+        bail!("帐号 {} 已存在！", this.to);
+    }
+
+    accounts.rename(&this.from, &this.to).await?;
+    eprintln!("帐号 {} 已重命名为 {}！", this.from, this.to);
+    Ok(())
+}
+
 #[derive(Parser, Clone)]
 pub(crate) struct SsAccountLoginCommand {
     /// 可选的 cookie，用于自动登录
     #[clap(short, long = "cookie")]
     cookies: Vec<String>,
+    /// 从文件导入 cookie，支持 cookies.txt、EditThisCookie 导出的 JSON 与 Cookie 请求头
+    #[clap(long, conflicts_with = "cookies")]
+    cookie_file: Option<PathBuf>,
+    /// 登录方式，指定 cookie 或 cookie 文件时忽略
+    #[clap(short, long, arg_enum, default_value = "tv")]
+    method: LoginMethod,
+    /// 扫码登录时将二维码另存为 PNG 图片
+    #[clap(long)]
+    save_qrcode: Option<PathBuf>,
     /// 帐号名称，在后续投稿时需要作为参数传递进来
     name: String,
 }
 
 #[handler(SsAccountLoginCommand)]
-async fn account_login(this: &SsAccountLoginCommand, config_root: &PathBuf) -> anyhow::Result<()> {
-    let account_path = config_root
-        .join("accounts")
-        .join(format!("{}.json", this.name));
-    if account_path.exists() {
+async fn account_login(
+    this: &SsAccountLoginCommand,
+    accounts: &AccountStore,
+    client_config: &ClientConfig,
+) -> anyhow::Result<()> {
+    if accounts.exists(&this.name) {
         bail!("帐号 {} 已存在！", this.name);
     }
 
-    let credential = if this.cookies.is_empty() {
-        let qrcode = Credential::get_qrcode().await?;
-        eprintln!(
-            "请打开以下链接登录：\n{}",
-            qrcode["data"]["url"].as_str().unwrap()
-        );
-        Credential::from_qrcode(qrcode).await?
-    } else {
+    let cookies = if let Some(cookie_file) = &this.cookie_file {
+        let content = fs::read_to_string(cookie_file)
+            .await
+            .with_context(|| format!("read cookie file {}", cookie_file.display()))?;
+        Some(CookieInfo::parse(&content)?)
+    } else if !this.cookies.is_empty() {
         let cookies: Vec<_> = this
             .cookies
             .iter()
             .filter_map(|c| CookieEntry::from_str(c).ok())
             .collect();
-        Credential::from_cookies(&CookieInfo::new(cookies)).await?
+        Some(CookieInfo::new(cookies))
+    } else {
+        None
+    };
+    let credential = match cookies {
+        Some(cookies) => {
+            cookies.validate()?;
+            Credential::from_cookies(client_config, &cookies).await?
+        }
+        None => login::login(client_config, this.method, this.save_qrcode.as_deref()).await?,
     };
 
-    fs::write(account_path, serde_json::to_string(&credential)?).await?;
-    let nickname = credential.get_nickname().await?;
+    accounts.save(&this.name, &credential).await?;
+    let nickname = credential.get_nickname(client_config).await?;
     eprintln!("帐号 {} 已登录！帐号名为：{nickname}", this.name);
     Ok(())
 }
@@ -784,16 +1280,51 @@ pub(crate) struct SsAccountLogoutCommand {
 #[handler(SsAccountLogoutCommand)]
 async fn account_logout(
     this: &SsAccountLogoutCommand,
-    config_root: &PathBuf,
+    accounts: &AccountStore,
 ) -> anyhow::Result<()> {
-    let account_path = config_root
-        .join("accounts")
-        .join(format!("{}.json", this.name));
-    if !account_path.exists() {
+    if !accounts.exists(&this.name) {
         bail!("帐号 {} 不存在！", this.name);
     }
 
-    fs::remove_file(account_path).await?;
+    accounts.remove(&this.name).await?;
     eprintln!("帐号 {} 已删除！", this.name);
     Ok(())
 }
+
+#[derive(Parser, Clone)]
+pub(crate) struct SsAccountEncryptCommand {
+    /// 解密凭据，恢复为明文保存
+    #[clap(long)]
+    decrypt: bool,
+    /// 待处理的帐号名称，留空时处理所有帐号
+    names: Vec<String>,
+}
+
+#[handler(SsAccountEncryptCommand)]
+async fn account_encrypt(
+    this: &SsAccountEncryptCommand,
+    accounts: &AccountStore,
+) -> anyhow::Result<()> {
+    let names = if this.names.is_empty() {
+        accounts.names().await?
+    } else {
+        this.names.clone()
+    };
+    let encrypt = !this.decrypt;
+    for name in names {
+        if !accounts.exists(&name) {
+            bail!("帐号 {name} 不存在！");
+        }
+        if accounts.is_encrypted(&name).await? == encrypt {
+            continue;
+        }
+        let credential = accounts.load(&name).await?;
+        accounts.save_as(&name, &credential, encrypt).await?;
+        if encrypt {
+            eprintln!("帐号 {name} 已加密！");
+        } else {
+            eprintln!("帐号 {name} 已解密！");
+        }
+    }
+    Ok(())
+}
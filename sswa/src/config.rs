@@ -1,6 +1,11 @@
-use anyhow::Context;
+use crate::account::AccountStore;
+use crate::rate::{self, Rate, RateSchedule};
+use anyhow::{bail, Context};
+use indicatif::{HumanBytes, MultiProgress};
 use serde::Deserialize;
-use ssup::UploadLine;
+use ssup::{ChunkBudget, ClientConfig, UploadLine};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -13,6 +18,42 @@ pub(crate) struct Config {
     scale_cover: Option<bool>,
     /// 提交失败的重试次数
     submit_retry: Option<u8>,
+    /// 单个分P同时上传的分块数
+    concurrency: Option<usize>,
+    /// 同时上传的分P数
+    part_concurrency: Option<usize>,
+    /// 所有分P同时上传的分块数，默认与 `concurrency` 相同
+    total_concurrency: Option<usize>,
+    /// 代理地址
+    proxy: Option<String>,
+    /// 扫码登录的超时时间（秒），为 0 时一直等待
+    login_timeout: Option<u64>,
+    /// 是否加密保存帐号凭据
+    encrypt_accounts: Option<bool>,
+    /// 帐号凭据的密钥文件，未设置环境变量 `SSWA_PASSPHRASE` 时使用
+    account_key_file: Option<PathBuf>,
+    /// 所有分P共享的上传限速
+    limit_rate: Option<Rate>,
+    /// 单个分P的上传限速
+    part_limit_rate: Option<Rate>,
+    /// 按时段调整的上传限速，覆盖 `limit-rate`
+    #[serde(default)]
+    limit_schedule: Vec<RateSchedule>,
+    /// 接口地址
+    #[serde(default)]
+    endpoints: Endpoints,
+}
+
+/// 接口地址，留空时使用 B 站的正式接口
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct Endpoints {
+    member: Option<String>,
+    passport: Option<String>,
+    tv_passport: Option<String>,
+    api: Option<String>,
+    /// 替换上传线路返回的上传地址
+    upos: Option<String>,
 }
 
 impl Config {
@@ -22,6 +63,17 @@ impl Config {
             default_user: None,
             scale_cover: None,
             submit_retry: None,
+            concurrency: None,
+            part_concurrency: None,
+            total_concurrency: None,
+            proxy: None,
+            login_timeout: None,
+            encrypt_accounts: None,
+            account_key_file: None,
+            limit_rate: None,
+            part_limit_rate: None,
+            limit_schedule: Vec::new(),
+            endpoints: Endpoints::default(),
         }
     }
 
@@ -33,17 +85,92 @@ impl Config {
         self.scale_cover.unwrap_or(false)
     }
 
-    pub(crate) async fn line(&self) -> anyhow::Result<UploadLine> {
+    pub(crate) fn part_concurrency(&self) -> usize {
+        self.part_concurrency.filter(|&c| c > 0).unwrap_or(3)
+    }
+
+    /// 帐号凭据的存储
+    pub(crate) fn accounts(&self, root: &Path) -> AccountStore {
+        AccountStore::new(
+            root,
+            self.encrypt_accounts.unwrap_or(false),
+            self.account_key_file.clone(),
+        )
+    }
+
+    /// 生成 ssup 客户端使用的配置
+    pub(crate) fn client_config(&self) -> ClientConfig {
+        let mut config = ClientConfig::default();
+        let endpoints = &self.endpoints;
+        if let Some(member) = &endpoints.member {
+            config.member_url = member.clone();
+        }
+        if let Some(passport) = &endpoints.passport {
+            config.passport_url = passport.clone();
+        }
+        if let Some(tv_passport) = &endpoints.tv_passport {
+            config.tv_passport_url = tv_passport.clone();
+        }
+        if let Some(api) = &endpoints.api {
+            config.api_url = api.clone();
+        }
+        config.upos_url = endpoints.upos.clone();
+        if let Some(concurrency) = self.concurrency.filter(|&c| c > 0) {
+            config.concurrency = concurrency;
+        }
+        let total_concurrency = self.total_concurrency.unwrap_or(config.concurrency);
+        config.chunk_budget = ChunkBudget::new(Some(total_concurrency));
+        config.proxy = self.proxy.clone();
+        if let Some(timeout) = self.login_timeout {
+            config.login_timeout = Some(timeout).filter(|&t| t > 0).map(Duration::from_secs);
+        }
+        config.part_rate_limit = self.part_limit_rate.map(|rate| rate.0);
+        config
+    }
+
+    /// 设置上传限速，命令行指定的限速优先于配置文件
+    pub(crate) fn limit_rate(&self, config: &ClientConfig, limit_rate: Option<Rate>) {
+        match limit_rate {
+            Some(rate) => config.rate_limiter.set_rate(Some(rate.0)),
+            None => rate::apply(&config.rate_limiter, self.limit_rate, &self.limit_schedule),
+        }
+    }
+
+    /// 获取可用的线路，自动选择时按测速结果由好到坏排列
+    pub(crate) async fn lines(
+        &self,
+        client_config: &ClientConfig,
+        progress: &MultiProgress,
+    ) -> anyhow::Result<Vec<UploadLine>> {
         let line = self.line.as_deref().unwrap_or("auto");
-        let line = match line {
-            "bda2" => UploadLine::bda2(),
-            "ws" => UploadLine::ws(),
-            "qn" => UploadLine::qn(),
-            "auto" => UploadLine::auto()
-                .await
-                .with_context(|| "auto select upload line")?,
-            _ => unimplemented!(),
+        let lines = match line {
+            "bda2" => vec![UploadLine::bda2()],
+            "ws" => vec![UploadLine::ws()],
+            "qn" => vec![UploadLine::qn()],
+            "auto" => {
+                let results = UploadLine::probe(client_config, 3, Duration::from_secs(5))
+                    .await
+                    .with_context(|| "auto select upload line")?;
+                if results.is_empty() {
+                    bail!("没有可用的线路！");
+                }
+                progress.println("线路测速结果：")?;
+                for result in results.iter() {
+                    let throughput = match result.throughput {
+                        Some(throughput) => format!("{}/s", HumanBytes(throughput as u64)),
+                        None => "-".to_string(),
+                    };
+                    progress.println(format!(
+                        "  {:<8} {:>6}ms {:>12}",
+                        result.line.name(),
+                        result.latency.as_millis(),
+                        throughput,
+                    ))?;
+                }
+                results.into_iter().map(|result| result.line).collect()
+            }
+            _ => bail!("未知的线路：{line}，可选值为 auto、bda2、ws 和 qn"),
         };
-        Ok(line)
+        Ok(lines)
     }
 }
@@ -6,7 +6,7 @@ use tempfile::{NamedTempFile, TempPath};
 
 pub(crate) fn get_duration<P: AsRef<Path>>(video_path: P) -> anyhow::Result<u32> {
     let command = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v",
             "error",
             "-show_entries",
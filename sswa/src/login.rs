@@ -0,0 +1,148 @@
+use anyhow::{bail, Context};
+use clap::ArgEnum;
+use futures::future::Either;
+use qrcode::{Color, QrCode};
+use ssup::{ClientConfig, Credential, QrcodeStatus};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// 终端中二维码四周留白的模块数
+const TERMINAL_QUIET_ZONE: usize = 2;
+/// 图片中二维码四周留白的模块数
+const PNG_QUIET_ZONE: usize = 4;
+/// 图片中每个模块的边长（像素）
+const PNG_SCALE: usize = 8;
+
+/// 登录方式
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub(crate) enum LoginMethod {
+    /// TV 端扫码登录
+    Tv,
+    /// 网页端扫码登录，只保存 Cookie，不受 TV 端登录的限制
+    Web,
+    /// 短信验证码登录
+    Sms,
+}
+
+/// 以指定方式登录
+///
+/// 扫码登录时在终端中显示二维码，可同时将二维码保存为 PNG 图片。
+pub(crate) async fn login(
+    config: &ClientConfig,
+    method: LoginMethod,
+    png: Option<&Path>,
+) -> anyhow::Result<Credential> {
+    let on_status = |status| match status {
+        QrcodeStatus::Generated { url } => show_qrcode(&url, png),
+        QrcodeStatus::Scanned => eprintln!("已扫码，请在手机上确认登录"),
+        QrcodeStatus::Expired => eprintln!("二维码已过期，正在重新生成"),
+    };
+    let login = match method {
+        LoginMethod::Tv => Either::Left(Credential::login_by_qrcode(config, on_status)),
+        LoginMethod::Web => Either::Right(Credential::login_by_web_qrcode(config, on_status)),
+        LoginMethod::Sms => return sms_login(config).await,
+    };
+    tokio::select! {
+        credential = login => match credential {
+            Err(ssup::Error::LoginTimeout(timeout)) => {
+                bail!("扫码登录超时（{} 秒）！", timeout.as_secs())
+            }
+            credential => Ok(credential?),
+        },
+        _ = tokio::signal::ctrl_c() => bail!("扫码登录已取消！"),
+    }
+}
+
+/// 短信验证码登录，手机号带有 `+区号 ` 前缀时使用对应的区号
+async fn sms_login(config: &ClientConfig) -> anyhow::Result<Credential> {
+    let question = requestty::Question::input("tel")
+        .message("请输入手机号（非中国大陆号码请加上区号，如 +852 12345678）")
+        .build();
+    let answer = requestty::prompt_one(question)?;
+    let tel = answer.as_string().unwrap_or_default().trim();
+    let (cid, tel) = match tel.strip_prefix('+').and_then(|tel| tel.split_once(' ')) {
+        Some((cid, tel)) => (
+            cid.parse().with_context(|| "parse country code")?,
+            tel.trim(),
+        ),
+        None => (86, tel),
+    };
+    let sms = Credential::send_sms(config, cid, tel).await?;
+
+    let question = requestty::Question::input("code")
+        .message("请输入收到的短信验证码")
+        .build();
+    let answer = requestty::prompt_one(question)?;
+    let code = answer.as_string().unwrap_or_default().trim();
+    Ok(Credential::from_sms(config, &sms, code).await?)
+}
+
+fn show_qrcode(url: &str, png: Option<&Path>) {
+    match QrCode::new(url) {
+        Ok(code) => {
+            eprintln!("请使用哔哩哔哩客户端扫描以下二维码登录：");
+            eprintln!("{}", render_terminal(&code));
+            if let Some(path) = png {
+                match save_png(&code, path) {
+                    Ok(()) => eprintln!("二维码已保存至 {}", path.display()),
+                    Err(e) => eprintln!("二维码保存失败：{e:#}"),
+                }
+            }
+        }
+        Err(e) => log::warn!("Failed to encode qrcode: {e}"),
+    }
+    eprintln!("无法扫码时也可打开以下链接登录：\n{url}");
+}
+
+/// 模块是否为浅色，留白区域视为浅色
+fn is_light(code: &QrCode, colors: &[Color], x: usize, y: usize, quiet_zone: usize) -> bool {
+    let width = code.width();
+    if x < quiet_zone || y < quiet_zone || x >= width + quiet_zone || y >= width + quiet_zone {
+        return true;
+    }
+    colors[(y - quiet_zone) * width + x - quiet_zone] == Color::Light
+}
+
+/// 以 Unicode 半角方块绘制二维码，每个字符对应上下两个模块
+///
+/// 浅色模块以前景色绘制，适用于深色背景的终端。
+fn render_terminal(code: &QrCode) -> String {
+    let colors = code.to_colors();
+    let size = code.width() + 2 * TERMINAL_QUIET_ZONE;
+    let mut output = String::new();
+    for y in (0..size).step_by(2) {
+        for x in 0..size {
+            let top = is_light(code, &colors, x, y, TERMINAL_QUIET_ZONE);
+            let bottom = y + 1 >= size || is_light(code, &colors, x, y + 1, TERMINAL_QUIET_ZONE);
+            output.push(match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// 将二维码保存为灰度 PNG 图片
+fn save_png(code: &QrCode, path: &Path) -> anyhow::Result<()> {
+    let colors = code.to_colors();
+    let size = (code.width() + 2 * PNG_QUIET_ZONE) * PNG_SCALE;
+    let mut data = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let light = is_light(code, &colors, x / PNG_SCALE, y / PNG_SCALE, PNG_QUIET_ZONE);
+            data.push(if light { 0xff } else { 0x00 });
+        }
+    }
+
+    let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
@@ -2,10 +2,13 @@ use crate::args::Args;
 use clap::Parser;
 use clap_handler::Handler;
 
+mod account;
 mod args;
 mod config;
 mod context;
 mod ffmpeg;
+mod login;
+mod rate;
 mod template;
 
 #[tokio::main]
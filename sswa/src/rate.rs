@@ -0,0 +1,120 @@
+use chrono::{Local, NaiveTime};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use ssup::RateLimiter;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 按时段调整限速的检查间隔
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 上传速率，单位为字节每秒
+///
+/// 支持 `K`、`M`、`G` 后缀，如 `500K`、`2M`、`1.5MB/s`，为 0 时不限速
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Rate(pub u64);
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rate = s.trim();
+        let rate = rate.strip_suffix("/s").unwrap_or(rate);
+        let rate = rate.strip_suffix(['B', 'b']).unwrap_or(rate);
+        let (number, multiplier) = match rate.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('K') => (&rate[..rate.len() - 1], 1u64 << 10),
+            Some('M') => (&rate[..rate.len() - 1], 1 << 20),
+            Some('G') => (&rate[..rate.len() - 1], 1 << 30),
+            _ => (rate, 1),
+        };
+        let number: f64 = number
+            .trim()
+            .parse()
+            .map_err(|_| format!("无效的速率：{s}"))?;
+        if number < 0.0 {
+            return Err(format!("无效的速率：{s}"));
+        }
+        Ok(Rate((number * multiplier as f64) as u64))
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RateValue {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match RateValue::deserialize(deserializer)? {
+            RateValue::Bytes(rate) => Ok(Rate(rate)),
+            RateValue::Text(rate) => rate.parse().map_err(D::Error::custom),
+        }
+    }
+}
+
+/// 指定时段内的限速
+#[derive(Deserialize, Clone)]
+pub(crate) struct RateSchedule {
+    /// 开始时间，如 `09:00`
+    #[serde(deserialize_with = "time")]
+    from: NaiveTime,
+    /// 结束时间，早于开始时间时表示跨越零点
+    #[serde(deserialize_with = "time")]
+    to: NaiveTime,
+    rate: Rate,
+}
+
+impl RateSchedule {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+fn time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let time = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&time, "%H:%M").map_err(D::Error::custom)
+}
+
+/// 当前时间应使用的速率，不在任何时段内时使用 `base`
+fn scheduled_rate(base: Option<Rate>, schedule: &[RateSchedule]) -> Option<u64> {
+    let now = Local::now().time();
+    schedule
+        .iter()
+        .find(|entry| entry.contains(now))
+        .map(|entry| entry.rate)
+        .or(base)
+        .map(|rate| rate.0)
+}
+
+/// 设置限速器的速率，配置了时段限速时在后台按时段调整
+pub(crate) fn apply(limiter: &RateLimiter, base: Option<Rate>, schedule: &[RateSchedule]) {
+    limiter.set_rate(scheduled_rate(base, schedule));
+    if schedule.is_empty() {
+        return;
+    }
+
+    let limiter = limiter.clone();
+    let schedule = schedule.to_vec();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULE_INTERVAL).await;
+            let rate = scheduled_rate(base, &schedule);
+            if rate != limiter.rate() {
+                log::info!("Upload rate limit changed to {rate:?}");
+                limiter.set_rate(rate);
+            }
+        }
+    });
+}
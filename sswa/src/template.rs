@@ -1,9 +1,10 @@
 use crate::context::CONTEXT;
-use chrono::NaiveDateTime;
+use chrono::DateTime;
 use date_time_parser::{DateParser, TimeParser};
 use serde::Deserialize;
-use ssup::video::{Subtitle, Video, VideoPart};
+use ssup::video::{EditVideo, Subtitle, Video, VideoPart};
 use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
 use std::process::exit;
 use tinytemplate::instruction::PathStep;
@@ -51,7 +52,7 @@ impl VideoTemplate {
     }
 
     /// 构建模板
-    pub(crate) fn build(&self, skip_level: u8) -> anyhow::Result<TinyTemplate> {
+    pub(crate) fn build(&self, skip_level: u8) -> anyhow::Result<TinyTemplate<'_>> {
         let mut template = TinyTemplate::new();
         // 常用 Formatter
         template.add_formatter("comma2cn", |input, output| {
@@ -105,7 +106,7 @@ impl VideoTemplate {
                             let ans = if self.variables.is_required(variable) {
                                 let description = match self.variables.description(variable) {
                                     Some(description) => format!("{description}({variable})"),
-                                    None => format!("{variable}"),
+                                    None => variable.to_string(),
                                 };
                                 if skip_level < 2 {
                                     // 用户输入变量
@@ -156,7 +157,8 @@ impl VideoTemplate {
 
         let display_time = match self.display_timestamp(template)? {
             Some(time) => {
-                let time = NaiveDateTime::from_timestamp(time + 60 * 60 * 8, 0);
+                let time = DateTime::from_timestamp(time + 60 * 60 * 8, 0)
+                    .ok_or_else(|| anyhow::anyhow!("定时投稿时间无效：{time}"))?;
                 time.format("%Y-%m-%d %H:%M:%S (UTC+8)").to_string()
             }
             None => "未设置".to_string(),
@@ -192,7 +194,7 @@ impl VideoTemplate {
 
     fn forward_source(&self, template: &TinyTemplate) -> String {
         if let Some(source) = &self.forward_source {
-            source.to_string(&template).unwrap()
+            source.to_string(template).unwrap()
         } else {
             String::new()
         }
@@ -209,7 +211,7 @@ impl VideoTemplate {
                     let time = TimeParser::parse(&time);
                     match (date, time) {
                         (Some(date), Some(time)) => {
-                            Some(date.and_time(time).timestamp() - 60 * 60 * 8)
+                            Some(date.and_time(time).and_utc().timestamp() - 60 * 60 * 8)
                         }
                         _ => anyhow::bail!("定时投稿时间解析失败！"),
                     }
@@ -276,6 +278,46 @@ impl VideoTemplate {
         })
     }
 
+    /// 按模板修改稿件的标题、简介、标签、转载来源与动态，返回发生变化的字段
+    pub(crate) fn update(
+        &self,
+        template: &TinyTemplate<'_>,
+        video: &mut EditVideo,
+    ) -> anyhow::Result<Vec<FieldChange>> {
+        let title = self.title.to_string(template)?;
+        if title.chars().count() >= 80 {
+            anyhow::bail!("标题不得超过80个字符");
+        }
+        let source = self.forward_source(template);
+        let copyright = if source.is_empty() { 1 } else { 2 };
+
+        let mut changes = Vec::new();
+        if video.copyright != copyright {
+            changes.push(FieldChange {
+                name: "类型",
+                old: copyright_name(video.copyright).to_string(),
+                new: copyright_name(copyright).to_string(),
+            });
+            video.copyright = copyright;
+        }
+        FieldChange::update(&mut changes, "标题", &mut video.title, title);
+        FieldChange::update(&mut changes, "来源", &mut video.source, source);
+        FieldChange::update(
+            &mut changes,
+            "简介",
+            &mut video.desc,
+            self.description.to_string(template)?,
+        );
+        FieldChange::update(&mut changes, "标签", &mut video.tag, self.tags(template)?);
+        FieldChange::update(
+            &mut changes,
+            "动态",
+            &mut video.dynamic,
+            self.dynamic_text.to_string(template)?,
+        );
+        Ok(changes)
+    }
+
     pub(crate) fn auto_cover(&self) -> bool {
         self.cover.is_empty() || self.cover.0 == "auto"
     }
@@ -283,28 +325,77 @@ impl VideoTemplate {
     pub(crate) fn video_prefix(&self, template: &TinyTemplate) -> Vec<PathBuf> {
         self.video_prefix
             .iter()
-            .map(|s| s.to_string(&template))
+            .map(|s| s.to_string(template))
             .filter_map(|s| match s {
                 Ok(s) if !s.is_empty() => Some(s),
                 _ => None,
             })
-            .map(|s| PathBuf::from(s))
+            .map(PathBuf::from)
             .collect()
     }
 
     pub(crate) fn video_suffix(&self, template: &TinyTemplate) -> Vec<PathBuf> {
         self.video_suffix
             .iter()
-            .map(|s| s.to_string(&template))
+            .map(|s| s.to_string(template))
             .filter_map(|s| match s {
                 Ok(s) if !s.is_empty() => Some(s),
                 _ => None,
             })
-            .map(|s| PathBuf::from(s))
+            .map(PathBuf::from)
             .collect()
     }
 }
 
+fn copyright_name(copyright: i64) -> &'static str {
+    if copyright == 2 {
+        "转载"
+    } else {
+        "自制"
+    }
+}
+
+/// 稿件信息中一个字段的变更
+pub(crate) struct FieldChange {
+    pub name: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl FieldChange {
+    /// 新值与原值不同时修改字段并记录变更
+    pub(crate) fn update(
+        changes: &mut Vec<FieldChange>,
+        name: &'static str,
+        field: &mut String,
+        value: String,
+    ) {
+        if *field != value {
+            let old = std::mem::replace(field, value.clone());
+            changes.push(FieldChange {
+                name,
+                old,
+                new: value,
+            });
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}：", self.name)?;
+        for (sign, value) in [('-', &self.old), ('+', &self.new)] {
+            if value.is_empty() {
+                writeln!(f, "  {sign} （空）")?;
+            }
+            for line in value.lines() {
+                writeln!(f, "  {sign} {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Deserialize, Default)]
 pub struct TemplateString(String);
 
@@ -354,7 +445,7 @@ impl TemplateVariables {
     }
 
     fn is_required(&self, key: &str) -> bool {
-        self.0.get(key).map_or(true, |v| v.is_required())
+        self.0.get(key).is_none_or(|v| v.is_required())
     }
 }
 
@@ -0,0 +1,149 @@
+#[path = "../../ssup/tests/common/mod.rs"]
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// 写入指向模拟服务的配置文件和帐号 `mock`
+fn setup(root: &Path, server: &MockServer) {
+    let url = server.url();
+    std::fs::write(
+        root.join("config.toml"),
+        format!(
+            r#"[endpoints]
+member = "{url}"
+passport = "{url}"
+tv-passport = "{url}"
+api = "{url}"
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::create_dir(root.join("accounts")).unwrap();
+    std::fs::write(
+        root.join("accounts").join("mock.json"),
+        common::credential_json().to_string(),
+    )
+    .unwrap();
+}
+
+fn sswa(root: &Path, passphrase: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sswa"))
+        .arg("--config-root")
+        .arg(root)
+        .args(args)
+        .env("SSWA_PASSPHRASE", passphrase)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn encrypt_account() {
+    let root = tempfile::tempdir().unwrap();
+    let accounts = root.path().join("accounts");
+    std::fs::create_dir(&accounts).unwrap();
+    let account = accounts.join("mock.json");
+    std::fs::write(&account, common::credential_json().to_string()).unwrap();
+
+    let output = sswa(root.path(), "secret", &["encrypt"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let encrypted = std::fs::read_to_string(&account).unwrap();
+    assert!(!encrypted.contains("mock_access_token"));
+
+    let output = sswa(root.path(), "wrong", &["encrypt", "--decrypt", "mock"]);
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read_to_string(&account).unwrap(), encrypted);
+
+    let output = sswa(root.path(), "secret", &["encrypt", "--decrypt", "mock"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let decrypted: Value = serde_json::from_slice(&std::fs::read(&account).unwrap()).unwrap();
+    assert_eq!(decrypted["token_info"]["access_token"], "mock_access_token");
+    assert_eq!(
+        decrypted["cookie_info"],
+        common::credential_json()["cookie_info"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn manage_accounts() {
+    let server = MockServer::start().await;
+    let root = tempfile::tempdir().unwrap();
+    setup(root.path(), &server);
+    let run = |args: &'static [&'static str]| {
+        let root = root.path().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let output = sswa(&root, "", args);
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stdout).unwrap()
+        })
+    };
+
+    let list = run(&["accounts", "--long"]).await.unwrap();
+    let line = list.lines().nth(1).unwrap();
+    let columns: Vec<_> = line.split('\t').collect();
+    assert_eq!(columns[..3], ["mock", "mock", "1"]);
+    assert_eq!(columns[5], "有效");
+
+    run(&["account", "refresh", "mock"]).await.unwrap();
+    let account = root.path().join("accounts").join("mock.json");
+    let refreshed: Value = serde_json::from_slice(&std::fs::read(&account).unwrap()).unwrap();
+    assert_eq!(
+        refreshed["token_info"]["access_token"],
+        "refreshed_access_token"
+    );
+
+    run(&["account", "rename", "mock", "renamed"])
+        .await
+        .unwrap();
+    assert_eq!(run(&["accounts"]).await.unwrap(), "renamed\n");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn login_with_cookie_file() {
+    let server = MockServer::start().await;
+    let root = tempfile::tempdir().unwrap();
+    setup(root.path(), &server);
+    let cookie_file = root.path().join("cookies.txt");
+    std::fs::write(
+        &cookie_file,
+        "Cookie: SESSDATA=mock_sessdata; bili_jct=mock_csrf; DedeUserID=1",
+    )
+    .unwrap();
+
+    let path = root.path().to_path_buf();
+    let output = tokio::task::spawn_blocking(move || {
+        let cookie_file = cookie_file.to_str().unwrap();
+        sswa(
+            &path,
+            "",
+            &["login", "--cookie-file", cookie_file, "imported"],
+        )
+    })
+    .await
+    .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(root.path().join("accounts").join("imported.json").exists());
+    let confirm = server.requests(
+        hyper::Method::POST,
+        "/x/passport-tv-login/h5/qrcode/confirm",
+    );
+    assert!(String::from_utf8_lossy(&confirm[0].body).contains("csrf=mock_csrf"));
+}
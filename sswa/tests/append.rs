@@ -0,0 +1,111 @@
+#[path = "../../ssup/tests/common/mod.rs"]
+mod common;
+
+use common::{MockServer, CHUNK_SIZE};
+use hyper::Method;
+use std::process::Command;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn append_part() {
+    let server = MockServer::start().await;
+    let root = tempfile::tempdir().unwrap();
+    let url = server.url();
+    std::fs::write(
+        root.path().join("config.toml"),
+        format!(
+            r#"line = "bda2"
+default-user = "mock"
+
+[endpoints]
+member = "{url}"
+passport = "{url}"
+tv-passport = "{url}"
+api = "{url}"
+upos = "{url}"
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::create_dir(root.path().join("accounts")).unwrap();
+    std::fs::write(
+        root.path().join("accounts").join("mock.json"),
+        common::credential_json().to_string(),
+    )
+    .unwrap();
+    let video = common::video_file(root.path(), "P2.mp4", CHUNK_SIZE * 2 + 1);
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sswa"));
+    command
+        .arg("--config-root")
+        .arg(root.path())
+        .args(["append", "-v", &format!("av{}", common::AID), "-n", "第二P"])
+        .arg(&video);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut chunks = server.chunks();
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![1, 2, 3]);
+
+    let edit = server.requests(Method::POST, "/x/vu/client/edit");
+    assert_eq!(edit.len(), 1);
+    let body = edit[0].json();
+    assert_eq!(body["aid"], common::AID);
+    let videos = body["videos"].as_array().unwrap();
+    assert_eq!(videos.len(), 2);
+    assert_eq!(videos[0]["filename"], "n000001");
+    assert_eq!(videos[0]["cid"], 100001);
+    assert_eq!(videos[1]["filename"], "mock");
+    assert_eq!(videos[1]["title"], "第二P");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reject_unknown_line() {
+    let server = MockServer::start().await;
+    let root = tempfile::tempdir().unwrap();
+    let url = server.url();
+    std::fs::write(
+        root.path().join("config.toml"),
+        format!(
+            r#"line = "bda"
+default-user = "mock"
+
+[endpoints]
+member = "{url}"
+passport = "{url}"
+tv-passport = "{url}"
+api = "{url}"
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::create_dir(root.path().join("accounts")).unwrap();
+    std::fs::write(
+        root.path().join("accounts").join("mock.json"),
+        common::credential_json().to_string(),
+    )
+    .unwrap();
+    let video = common::video_file(root.path(), "P2.mp4", CHUNK_SIZE);
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sswa"));
+    command
+        .arg("--config-root")
+        .arg(root.path())
+        .args(["append", "-v", &format!("av{}", common::AID)])
+        .arg(&video);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("未知的线路：bda"), "{stderr}");
+    assert!(server.chunks().is_empty());
+}
@@ -0,0 +1,149 @@
+#[path = "../../ssup/tests/common/mod.rs"]
+mod common;
+
+use common::MockServer;
+use hyper::Method;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// 创建指向模拟服务的配置目录，并写入名为 `edit` 的模板
+fn config_root(server: &MockServer, template: &str) -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    let url = server.url();
+    std::fs::write(
+        root.path().join("config.toml"),
+        format!(
+            r#"default-user = "mock"
+
+[endpoints]
+member = "{url}"
+passport = "{url}"
+tv-passport = "{url}"
+api = "{url}"
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::create_dir(root.path().join("accounts")).unwrap();
+    std::fs::write(
+        root.path().join("accounts").join("mock.json"),
+        common::credential_json().to_string(),
+    )
+    .unwrap();
+    std::fs::create_dir(root.path().join("templates")).unwrap();
+    std::fs::write(root.path().join("templates").join("edit.toml"), template).unwrap();
+    root
+}
+
+async fn edit(root: &Path, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sswa"));
+    command
+        .arg("--config-root")
+        .arg(root)
+        .args(["edit", "-v", &format!("av{}", common::AID), "-t", "edit"])
+        .args(args);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn edit_from_template() {
+    let server = MockServer::start().await;
+    let root = config_root(
+        &server,
+        r#"title = "{{episode}} 新标题"
+description = "{{$bvid}}"
+tid = 17
+cover = "cover.jpg"
+tags = ["mock", "{{episode}}"]
+"#,
+    );
+
+    let output = edit(root.path(), &["--var", "episode=第1期", "-yyy"]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("  - mock\n  + 第1期 新标题\n"), "{stderr}");
+
+    // 未指定 `--cover` 时保留原封面
+    assert!(server
+        .requests(Method::POST, "/x/vu/web/cover/up")
+        .is_empty());
+    let edit = server.requests(Method::POST, "/x/vu/client/edit");
+    assert_eq!(edit.len(), 1);
+    let body = edit[0].json();
+    assert_eq!(body["aid"], common::AID);
+    assert_eq!(body["title"], "第1期 新标题");
+    assert_eq!(body["desc"], "BV17x411w7KC");
+    assert_eq!(body["tag"], "mock,第1期");
+    assert_eq!(body["cover"], "https://i0.hdslb.com/bfs/archive/mock.jpg");
+    assert_eq!(body["videos"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skip_unchanged_archive() {
+    let server = MockServer::start().await;
+    let root = config_root(
+        &server,
+        r#"title = "mock"
+description = ""
+tid = 17
+cover = "cover.jpg"
+tags = ["mock"]
+"#,
+    );
+
+    let output = edit(root.path(), &["-yyy"]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("稿件信息没有变化！"), "{stderr}");
+    assert!(server
+        .requests(Method::POST, "/x/vu/client/edit")
+        .is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replace_cover() {
+    let server = MockServer::start().await;
+    let root = config_root(
+        &server,
+        r#"title = "mock"
+description = ""
+tid = 17
+cover = "{{$config_root}}/cover.jpg"
+tags = ["mock"]
+"#,
+    );
+    std::fs::write(root.path().join("cover.jpg"), b"cover").unwrap();
+
+    edit(root.path(), &["--cover", "-yyy"]).await;
+    assert_eq!(server.requests(Method::POST, "/x/vu/web/cover/up").len(), 1);
+    assert_eq!(server.requests(Method::POST, "/x/vu/client/edit").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_skips_confirm() {
+    let server = MockServer::start().await;
+    let root = config_root(
+        &server,
+        r#"title = "新标题"
+description = ""
+tid = 17
+cover = "cover.jpg"
+tags = ["mock"]
+"#,
+    );
+
+    // 未指定 `-y` 时也不应等待确认
+    let output = edit(root.path(), &["--dry-run"]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("  - mock\n  + 新标题\n"), "{stderr}");
+    assert!(server
+        .requests(Method::POST, "/x/vu/client/edit")
+        .is_empty());
+}
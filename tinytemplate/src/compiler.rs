@@ -8,7 +8,7 @@ use crate::instruction::{Instruction, Path, PathStep};
 use std::cmp::min;
 
 /// The end point of a branch or goto instruction is not known.
-const UNKNOWN: usize = std::usize::MAX;
+const UNKNOWN: usize = usize::MAX;
 
 /// The compiler keeps a stack of the open blocks so that it can ensure that blocks are closed in
 /// the right order. The Block type is a simple enumeration of the kinds of blocks that could be
@@ -193,7 +193,7 @@ impl<'template> TemplateCompiler<'template> {
                     Err(_) => PathStep::Name(s),
                 })
                 .collect::<Vec<_>>())
-        } else if KNOWN_KEYWORDS.iter().any(|k| *k == text) {
+        } else if KNOWN_KEYWORDS.contains(&text) {
             Ok(vec![PathStep::Name(text)])
         } else {
             Err(self.parse_error(text, format!("Invalid keyword name '{}'", text)))
@@ -273,15 +273,9 @@ impl<'template> TemplateCompiler<'template> {
             self.remaining_text
         };
 
-        let position_value = search_substr
-            .find("{{")
-            .unwrap_or_else(|| search_substr.len());
-        let position_block = search_substr
-            .find("{%")
-            .unwrap_or_else(|| search_substr.len());
-        let position_comment = search_substr
-            .find("{#")
-            .unwrap_or_else(|| search_substr.len());
+        let position_value = search_substr.find("{{").unwrap_or(search_substr.len());
+        let position_block = search_substr.find("{%").unwrap_or(search_substr.len());
+        let position_comment = search_substr.find("{#").unwrap_or(search_substr.len());
         let mut position = min(position_value, min(position_block, position_comment));
         if escaped {
             position += 2;
@@ -8,6 +8,7 @@ use std::fmt;
 
 /// Enum representing the potential errors that TinyTemplate can encounter.
 #[derive(Debug)]
+#[allow(clippy::manual_non_exhaustive)]
 pub enum Error {
     ParseError {
         msg: String,
@@ -112,7 +113,7 @@ impl StdError for Error {
             Error::ParseError { .. } => "ParseError",
             Error::RenderError { .. } => "RenderError",
             Error::SerdeError { .. } => "SerdeError",
-            Error::GenericError { msg } => &msg,
+            Error::GenericError { msg } => msg,
             Error::StdFormatError { .. } => "StdFormatError",
             Error::CalledTemplateError { .. } => "CalledTemplateError",
             Error::CalledFormatterError { .. } => "CalledFormatterError",
@@ -1,13 +1,13 @@
-use std::ops::Deref;
+//! TinyTemplate implements a simple bytecode interpreter for its template engine. Instructions
+//! for this interpreter are represented by the Instruction enum and typically contain various
+//! parameters such as the path to context values or name strings.
+//!
+//! In TinyTemplate, the template string itself is assumed to be statically available (or at least
+//! longer-lived than the TinyTemplate instance) so paths and instructions simply borrow string
+//! slices from the template text. These string slices can then be appended directly to the output
+//! string.
 
-/// TinyTemplate implements a simple bytecode interpreter for its template engine. Instructions
-/// for this interpreter are represented by the Instruction enum and typically contain various
-/// parameters such as the path to context values or name strings.
-///
-/// In TinyTemplate, the template string itself is assumed to be statically available (or at least
-/// longer-lived than the TinyTemplate instance) so paths and instructions simply borrow string
-/// slices from the template text. These string slices can then be appended directly to the output
-/// string.
+use std::ops::Deref;
 
 /// Enum for a step in a path which optionally contains a parsed index.
 #[derive(Eq, PartialEq, Debug, Clone)]
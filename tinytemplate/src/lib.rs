@@ -199,7 +199,7 @@ impl<'template> TinyTemplate<'template> {
 
     /// Changes the default formatter from [`format`](fn.format.html) to `formatter`. Useful in combination with [`format_unescaped`](fn.format_unescaped.html) to deactivate HTML-escaping
     pub fn set_default_formatter(&mut self, formatter: ValueFormatter) {
-        self.default_formatter = Box::new(formatter);
+        *self.default_formatter = formatter;
     }
 
     /// Register the given formatter function under the given name.
@@ -208,11 +208,7 @@ impl<'template> TinyTemplate<'template> {
     }
 
     pub fn get_paths(&self) -> Vec<&Path<'template>> {
-        self.templates
-            .values()
-            .map(|s| s.paths())
-            .flatten()
-            .collect()
+        self.templates.values().flat_map(|s| s.paths()).collect()
     }
 
     /// Render the template with the given name using the given context object. The context
@@ -227,7 +223,7 @@ impl<'template> TinyTemplate<'template> {
                 &value,
                 &self.templates,
                 &self.formatters,
-                &*self.default_formatter,
+                &self.default_formatter,
             ),
             None => Err(Error::GenericError {
                 msg: format!("Unknown template '{}'", template),
@@ -251,7 +247,7 @@ mod test {
         name: String,
     }
 
-    static TEMPLATE: &'static str = "Hello {{name}}!";
+    static TEMPLATE: &str = "Hello {{name}}!";
 
     #[test]
     pub fn test_set_default_formatter() {
@@ -71,7 +71,7 @@ impl<'render, 'template> RenderContext<'render, 'template> {
                 }
             }
 
-            let step: &str = &*step;
+            let step: &str = step;
 
             match current.get(step) {
                 Some(next) => current = next,
@@ -96,7 +96,7 @@ impl<'render, 'template> RenderContext<'render, 'template> {
 
     /// Look up the root context object
     fn lookup_root(&self) -> Result<&'render Value> {
-        match self.context_stack.get(0) {
+        match self.context_stack.first() {
             Some(ContextElement::Object(obj)) => Ok(obj),
             Some(_) => {
                 panic!("Expected Object value at root of context stack, but was something else.")
@@ -175,7 +175,7 @@ impl<'template> Template<'template> {
                         // Currently we just hard-code the special @-keywords and have special
                         // lookup functions to use them because there are lifetime complexities with
                         // looking up values that don't live for as long as the given context object.
-                        let first: &str = &*first;
+                        let first: &str = first;
                         match first {
                             "@index" => {
                                 write!(output, "{}", render_context.lookup_index()?.0).unwrap()
@@ -216,8 +216,8 @@ impl<'template> Template<'template> {
                 Instruction::Branch(path, negate, target) => {
                     let first = path.first().unwrap();
                     let mut truthy = if first.starts_with('@') {
-                        let first: &str = &*first;
-                        match &*first {
+                        let first: &str = first;
+                        match first {
                             "@index" => render_context.lookup_index()?.0 != 0,
                             "@first" => render_context.lookup_index()?.0 == 0,
                             "@last" => {
@@ -264,7 +264,7 @@ impl<'template> Template<'template> {
                             render_context.context_stack.push(ContextElement::Iteration(
                                 name,
                                 &Value::Null,
-                                ::std::usize::MAX,
+                                usize::MAX,
                                 arr.len(),
                                 arr.iter(),
                             ))
@@ -824,7 +824,7 @@ mod test {
     fn test_root_print() {
         let template = compile("{{ @root }}");
         let context = "Hello World!";
-        let context = ::serde_json::to_value(&context).unwrap();
+        let context = ::serde_json::to_value(context).unwrap();
         let template_registry = other_templates();
         let formatter_registry = formatters();
         let string = template
@@ -842,7 +842,7 @@ mod test {
     fn test_root_branch() {
         let template = compile("{% if @root %}Hello World!{% endif %}");
         let context = true;
-        let context = ::serde_json::to_value(&context).unwrap();
+        let context = ::serde_json::to_value(context).unwrap();
         let template_registry = other_templates();
         let formatter_registry = formatters();
         let string = template
@@ -878,7 +878,7 @@ mod test {
     fn test_number_truthiness_zero() {
         let template = compile("{% if @root %}truthy{%else%}not truthy{% endif %}");
         let context = 0;
-        let context = ::serde_json::to_value(&context).unwrap();
+        let context = ::serde_json::to_value(context).unwrap();
         let template_registry = other_templates();
         let formatter_registry = formatters();
         let string = template
@@ -896,7 +896,7 @@ mod test {
     fn test_number_truthiness_one() {
         let template = compile("{% if @root %}truthy{%else%}not truthy{% endif %}");
         let context = 1;
-        let context = ::serde_json::to_value(&context).unwrap();
+        let context = ::serde_json::to_value(context).unwrap();
         let template_registry = other_templates();
         let formatter_registry = formatters();
         let string = template